@@ -17,6 +17,15 @@ if [ -n "$PERMIT_ROOT_LOGIN" ]; then
     ARGS="$ARGS -o PermitRootLogin=$PERMIT_ROOT_LOGIN"
 fi
 
+if [ -n "$ACCEPT_ENV" ]; then
+    ARGS="$ARGS -o AcceptEnv=$ACCEPT_ENV"
+fi
+
+# `send()` uploads over sftp
+if ! grep -q "^Subsystem" /etc/ssh/sshd_config; then
+    echo "Subsystem sftp internal-sftp" >> /etc/ssh/sshd_config
+fi
+
 if [ -n "$PASSWORD_AUTHENTICATION" ]; then
     ARGS="$ARGS -o PasswordAuthentication=$PASSWORD_AUTHENTICATION"
 fi
@@ -42,4 +51,15 @@ else
     chmod 600 $HOME/.ssh/authorized_keys
 fi
 
+# user the tests become with sudo or su
+if [ -n "$BECOME_USER" ]; then
+    adduser -S $BECOME_USER -s /bin/sh
+    echo "$BECOME_USER:$BECOME_PASSWORD" | chpasswd 2>/dev/null
+    echo "$USERNAME ALL=($BECOME_USER) ALL" > /etc/sudoers.d/ettac
+fi
+
+# deploy path of the test scripts
+mkdir -p /var/www/ettac
+chown $USERNAME:$USERNAME /var/www/ettac
+
 /usr/sbin/sshd -D $ARGS
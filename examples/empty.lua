@@ -1,6 +1,6 @@
 function setup()
     default({
-        recipe = Recipe.new,
+        recipe = Recipe,
         repository = "https://github.com/mbenoukaiss/ettac.git",
         keep_releases = 3,
         persistent_files = { ".env" },
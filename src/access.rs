@@ -1,116 +1,672 @@
 use crate::Error;
-use crate::context::{AuthMethod, SshCredentials};
+use crate::context::{AuthMethod, BecomeMethod, SshCredentials};
 use crate::impl_error_try;
-use libssh_rs::{Session, SshKey, SshOption};
-use std::fmt::Debug;
-use std::io::Read;
-use std::process::Command;
+use crate::interrupt;
+use libssh_rs::{AuthStatus, Channel, FileType, OpenFlags, Session, Sftp, SshKey, SshOption};
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Display, Formatter};
+use std::fs;
+use std::io::{self, ErrorKind, Read, Write};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::CommandExt;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 use thiserror::Error as ThisError;
 
 const UNKNOWN_STATUS: i32 = -42398;
+const READ_POLL_INTERVAL: Duration = Duration::from_millis(200);
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(30);
 
 #[derive(ThisError, Debug)]
 pub enum AccessError {
-    #[error("Directory not found")]
-    DirectoryNotFound = 1,
+    #[error("Directory `{0}` not found")]
+    DirectoryNotFound(String),
+    #[error("`{0}` is not a directory")]
+    NotADirectory(String),
+    #[error("Permission denied to enter directory `{0}`")]
+    PermissionDenied(String),
+    #[error("No `sh` shell found to run the command")]
+    ShellNotFound,
+    #[error("The shell could not run the command (status {status}): {stderr}")]
+    ShellFailed { status: i32, stderr: String },
+    #[error("A password is required to become another user")]
+    BecomePasswordRequired,
+    #[error("The password to become another user was rejected")]
+    BecomePasswordRejected,
+    #[error(
+        "`su` cannot be used on local hosts as it only reads the password from a terminal, use `sudo` instead"
+    )]
+    LocalSu,
 }
 
 impl_error_try!(AccessError);
 
-impl AccessError {
-    pub const INTERNAL_ERROR_PREFIX: &'static str = "__ettac_internal_error::";
-
-    pub fn format(error: AccessError) -> String {
-        let mut output = String::new();
-        output.push_str(Self::INTERNAL_ERROR_PREFIX);
-        output.push_str((error as u8).to_string().as_str());
-
-        output
-    }
-
-    pub fn is(error: &str) -> bool {
-        error.contains(Self::INTERNAL_ERROR_PREFIX)
-    }
-}
-
-impl From<&String> for AccessError {
-    fn from(error: &String) -> Self {
-        let prefix = Self::INTERNAL_ERROR_PREFIX;
-        let code = error
-            .split_at(error.find(prefix).unwrap() + prefix.len())
-            .1
-            .as_bytes()[0];
-
-        match code {
-            1 => AccessError::DirectoryNotFound,
-            _ => panic!("unknown access error code: {}", code),
-        }
-    }
-}
-
 pub enum Access {
     Local(String),
-    Remote(String, Session),
+    Remote(String, Session, Option<Duration>),
+    /// Stands for the host during a dry run, nothing can run through it
+    DryRun(String),
 }
 
 impl Debug for Access {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Access::Local(path) => write!(f, "Access::Local({})", path),
-            Access::Remote(path, _) => write!(f, "Access::Remote({})", path),
+            Access::Remote(path, _, _) => write!(f, "Access::Remote({})", path),
+            Access::DryRun(path) => write!(f, "Access::DryRun({})", path),
         }
     }
 }
 
 impl Access {
     pub fn run(&self, cmd: &str) -> Result<CommandResult, Error> {
+        self.run_with(
+            &CommandLine::Shell(cmd.to_string()),
+            &CommandOptions::default(),
+        )
+    }
+
+    pub fn run_with(
+        &self,
+        cmd: &CommandLine,
+        options: &CommandOptions,
+    ) -> Result<CommandResult, Error> {
+        let dir = self.resolve_dir(options.cwd.as_deref());
+        let escalation = options.escalation.as_ref();
+
+        if cmd.is_empty() {
+            Err(Error::EmptyCommand)?
+        }
+
+        for name in options.env.keys() {
+            if !is_env_name(name) {
+                Err(Error::InvalidEnvName(name.clone()))?
+            }
+        }
+
         match self {
-            Access::Local(path) => {
-                let Some(args) = shlex::split(cmd) else {
-                    return Err(Error::UnparseableCommand(cmd.to_string()));
+            Access::DryRun(_) => Err(Error::DryRun),
+            Access::Local(_) => {
+                if escalation.is_some_and(|e| e.method == BecomeMethod::Su) {
+                    Err(AccessError::LocalSu)?
+                }
+
+                //the command goes through the same wrapper as on remote
+                //hosts so it fails the same way when it can not start
+                let cmd = cmd.to_shell()?;
+                let mut command = Command::new("/bin/sh");
+                match escalation {
+                    //sudo and su reset the environment so it is set from
+                    //within the escalated shell instead
+                    Some(escalation) => {
+                        let cmd = escalation.wrap(&export_env(&options.env, &cmd)?)?;
+                        command.arg("-c").arg(Wrapper::wrap(&dir, &cmd)?);
+                    }
+                    None => {
+                        command
+                            .arg("-c")
+                            .arg(Wrapper::wrap(&dir, &cmd)?)
+                            .envs(&options.env);
+                    }
+                }
+
+                //the command gets its own process group so whatever it
+                //started is stopped along with it
+                command.process_group(0);
+
+                let result = match escalation {
+                    Some(escalation) => run_local_escalated(command, escalation, options.deadline),
+                    None => run_local(command, options.deadline),
                 };
 
-                let output = Command::new(&args[0])
-                    .current_dir(path)
-                    .args(&args[1..])
-                    .output()?;
+                let result = match result {
+                    Err(Error::Io(err)) if err.kind() == ErrorKind::NotFound => {
+                        Err(AccessError::ShellNotFound)?
+                    }
+                    result => result?,
+                };
 
                 Ok(CommandResult {
-                    status: output.status.code().unwrap_or(UNKNOWN_STATUS),
-                    stdout: String::from_utf8_lossy(&output.stdout).to_string(),
-                    stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+                    stdout: Wrapper::output(result.status, &dir, &result.stdout, &result.stderr)?,
+                    ..result
                 })
             }
-            Access::Remote(path, sess) => {
+            Access::Remote(_, sess, keepalive) => {
                 let channel = sess.new_channel()?;
                 channel.open_session()?;
-                channel.request_exec(&format!(
-                    "if cd {}; then {}; else >&2 echo '{}'; exit; fi",
-                    path,
-                    cmd,
-                    AccessError::format(AccessError::DirectoryNotFound),
-                ))?;
 
-                channel.send_eof()?;
+                let cmd = cmd.to_shell()?;
+                let cmd = match escalation {
+                    Some(escalation) => escalation.wrap(&export_env(&options.env, &cmd)?)?,
+                    None => {
+                        //servers only accept the variables listed in their
+                        //AcceptEnv option, the other ones are exported
+                        let refused_env = options
+                            .env
+                            .iter()
+                            .filter(|(name, value)| channel.request_env(name, value).is_err())
+                            .map(|(name, value)| (name.clone(), value.clone()))
+                            .collect::<BTreeMap<String, String>>();
 
-                let mut stdout = String::new();
-                channel.stdout().read_to_string(&mut stdout)?;
+                        export_env(&refused_env, &cmd)?
+                    }
+                };
 
-                let mut stderr = String::new();
-                channel.stderr().read_to_string(&mut stderr)?;
+                //su only reads passwords from a terminal
+                let pty = escalation.is_some_and(|e| e.method == BecomeMethod::Su);
+                if pty {
+                    channel.request_pty("dumb", 80, 24)?;
+                }
+
+                channel.request_exec(&Wrapper::wrap(&dir, &cmd)?)?;
 
-                if AccessError::is(&stderr) {
-                    AccessError::from(&stderr)?
+                //with an escalation the input is kept open until the password
+                //has been written or is known to not be needed
+                if escalation.is_none() {
+                    channel.send_eof()?;
                 }
 
+                let mut handshake = escalation.map(|escalation| Handshake::new(escalation, pty));
+                let (stdout, stderr) = read_output(
+                    sess,
+                    &channel,
+                    *keepalive,
+                    options.deadline,
+                    handshake.as_mut(),
+                )?;
+                let status = channel.get_exit_status().unwrap_or(UNKNOWN_STATUS);
+
                 Ok(CommandResult {
-                    status: channel.get_exit_status().unwrap_or(UNKNOWN_STATUS),
-                    stdout,
+                    status,
+                    stdout: Wrapper::output(status, &dir, &stdout, &stderr)?,
                     stderr,
                 })
             }
         }
     }
+
+    /// Copies a local file or directory to `dest`, directories are copied
+    /// with their content and symlinks are recreated rather than followed
+    pub fn upload(&self, from: &Path, dest: &str) -> Result<(), Error> {
+        if let Some(parent) = Path::new(dest).parent() {
+            self.run_argv(&["mkdir", "-p", &parent.to_string_lossy()])?;
+        }
+
+        match self {
+            Access::DryRun(_) => Err(Error::DryRun),
+            Access::Local(_) => copy_local(from, Path::new(dest)),
+            Access::Remote(_, session, _) => self.copy_remote(&session.sftp()?, from, dest),
+        }
+    }
+
+    fn copy_remote(&self, sftp: &Sftp, from: &Path, dest: &str) -> Result<(), Error> {
+        interrupt::check()?;
+
+        let metadata = fs::symlink_metadata(from)?;
+        let mode = metadata.permissions().mode() & 0o7777;
+
+        if metadata.is_symlink() {
+            let target = fs::read_link(from)?;
+            self.run_argv(&["ln", "-sfn", &target.to_string_lossy(), dest])?;
+        } else if metadata.is_dir() {
+            let exists = sftp
+                .metadata(dest)
+                .is_ok_and(|dest| matches!(dest.file_type(), Some(FileType::Directory)));
+
+            if !exists {
+                sftp.create_dir(dest, mode)?;
+            }
+
+            for entry in fs::read_dir(from)? {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().to_string();
+                self.copy_remote(sftp, &entry.path(), &format!("{}/{}", dest, name))?;
+            }
+        } else {
+            let flags = OpenFlags::WRITE_ONLY | OpenFlags::CREATE | OpenFlags::TRUNCATE;
+            let mut file = sftp.open(dest, flags, mode)?;
+            io::copy(&mut fs::File::open(from)?, &mut file)?;
+
+            //the mode given when creating the file is masked by the umask
+            sftp.chmod(dest, mode)?;
+        }
+
+        Ok(())
+    }
+
+    fn run_argv(&self, args: &[&str]) -> Result<(), Error> {
+        let command = CommandLine::Argv(args.iter().map(|arg| arg.to_string()).collect());
+
+        let result = self.run_with(&command, &CommandOptions::default())?;
+        if result.status != 0 {
+            Err(Error::CommandFailed {
+                command: command.to_string(),
+                status: result.status,
+                stderr: result.stderr.trim_end().to_string(),
+            })?
+        }
+
+        Ok(())
+    }
+
+    /// Relative working directories are resolved from the access base path
+    pub fn resolve_dir(&self, cwd: Option<&str>) -> String {
+        let base = match self {
+            Access::Local(path) => path,
+            Access::Remote(path, _, _) => path,
+            Access::DryRun(path) => path,
+        };
+
+        match cwd {
+            Some(cwd) if cwd.starts_with('/') => cwd.to_string(),
+            Some(cwd) => format!("{}/{}", base.trim_end_matches('/'), cwd),
+            None => base.clone(),
+        }
+    }
+}
+
+fn copy_local(from: &Path, dest: &Path) -> Result<(), Error> {
+    interrupt::check()?;
+
+    let metadata = fs::symlink_metadata(from)?;
+
+    if metadata.is_symlink() {
+        if fs::symlink_metadata(dest).is_ok() {
+            fs::remove_file(dest)?;
+        }
+
+        std::os::unix::fs::symlink(fs::read_link(from)?, dest)?;
+    } else if metadata.is_dir() {
+        fs::create_dir_all(dest)?;
+        fs::set_permissions(dest, metadata.permissions())?;
+
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_local(&entry.path(), &dest.join(entry.file_name()))?;
+        }
+    } else {
+        fs::copy(from, dest)?;
+    }
+
+    Ok(())
+}
+
+/// Checks the working directory of the host before running the command. A
+/// marker is printed right before handing over to the command so the exit
+/// status is the one of the wrapper when stdout does not contain it, nothing
+/// the command outputs can be mistaken for a wrapper failure.
+/// Whatever the login shell prints before the marker, such as a banner, is
+/// dropped.
+struct Wrapper;
+
+impl Wrapper {
+    const STARTED: &'static str = "__ettac_started__";
+    const DIRECTORY_NOT_FOUND: i32 = 70;
+    const NOT_A_DIRECTORY: i32 = 71;
+    const PERMISSION_DENIED: i32 = 72;
+    const SHELL_NOT_FOUND: i32 = 73;
+
+    fn wrap(dir: &str, cmd: &str) -> Result<String, Error> {
+        let dir = quote(dir)?;
+
+        Ok(format!(
+            "if [ ! -e {dir} ]; then exit {}; fi; \
+             if [ ! -d {dir} ]; then exit {}; fi; \
+             cd -- {dir} 2>/dev/null || exit {}; \
+             command -v sh >/dev/null 2>&1 || exit {}; \
+             printf '%s' {}; exec sh -c {}",
+            Self::DIRECTORY_NOT_FOUND,
+            Self::NOT_A_DIRECTORY,
+            Self::PERMISSION_DENIED,
+            Self::SHELL_NOT_FOUND,
+            Self::STARTED,
+            quote(cmd)?,
+        ))
+    }
+
+    /// Output of the command, from the first marker on as the command can
+    /// only start once the wrapper printed it
+    fn output(status: i32, dir: &str, stdout: &str, stderr: &str) -> Result<String, AccessError> {
+        match stdout.find(Self::STARTED) {
+            Some(start) => Ok(stdout[start + Self::STARTED.len()..].to_string()),
+            None => Err(Self::error(status, dir, stderr)),
+        }
+    }
+
+    /// Error of a wrapper which did not reach the command, any other status
+    /// means the login shell of the user could not run the wrapper at all
+    fn error(status: i32, dir: &str, stderr: &str) -> AccessError {
+        match status {
+            Self::DIRECTORY_NOT_FOUND => AccessError::DirectoryNotFound(dir.to_string()),
+            Self::NOT_A_DIRECTORY => AccessError::NotADirectory(dir.to_string()),
+            Self::PERMISSION_DENIED => AccessError::PermissionDenied(dir.to_string()),
+            Self::SHELL_NOT_FOUND => AccessError::ShellNotFound,
+            status => AccessError::ShellFailed {
+                status,
+                stderr: stderr.trim_end().to_string(),
+            },
+        }
+    }
+}
+
+/// Commands are either interpreted by a shell, which allows pipes and
+/// redirections, or executed directly from a list of arguments
+#[derive(Clone, Debug, PartialEq)]
+pub enum CommandLine {
+    Shell(String),
+    Argv(Vec<String>),
+}
+
+impl CommandLine {
+    pub fn is_empty(&self) -> bool {
+        match self {
+            CommandLine::Shell(cmd) => cmd.trim().is_empty(),
+            CommandLine::Argv(args) => args.is_empty(),
+        }
+    }
+
+    /// Command as it can be given to a shell, arguments are quoted
+    pub fn to_shell(&self) -> Result<String, Error> {
+        match self {
+            CommandLine::Shell(cmd) => Ok(cmd.clone()),
+            CommandLine::Argv(args) => shlex::try_join(args.iter().map(String::as_str))
+                .map_err(|_| Error::UnparseableCommand(args.join(" "))),
+        }
+    }
+}
+
+impl Display for CommandLine {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let cmd = match self {
+            CommandLine::Shell(cmd) => cmd.clone(),
+            CommandLine::Argv(args) => self.to_shell().unwrap_or_else(|_| args.join(" ")),
+        };
+
+        write!(f, "{}", cmd)
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CommandOptions {
+    pub cwd: Option<String>,
+    pub env: BTreeMap<String, String>,
+    pub escalation: Option<Escalation>,
+    /// Point after which the command is stopped
+    pub deadline: Option<Instant>,
+}
+
+fn is_env_name(name: &str) -> bool {
+    let mut chars = name.chars();
+
+    chars
+        .next()
+        .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+        && chars.all(|char| char.is_ascii_alphanumeric() || char == '_')
+}
+
+pub fn quote(value: &str) -> Result<String, Error> {
+    shlex::try_quote(value)
+        .map(|quoted| quoted.to_string())
+        .map_err(|_| Error::UnparseableCommand(value.to_string()))
+}
+
+/// Prefixes the command with an export of the variables
+fn export_env(env: &BTreeMap<String, String>, cmd: &str) -> Result<String, Error> {
+    if env.is_empty() {
+        return Ok(cmd.to_string());
+    }
+
+    let mut exports = Vec::with_capacity(env.len());
+    for (name, value) in env {
+        exports.push(format!("{}={}", name, quote(value)?));
+    }
+
+    Ok(format!("export {}; {}", exports.join(" "), cmd))
+}
+
+/// Runs a command as another user with sudo or su
+#[derive(Clone, Debug)]
+pub struct Escalation {
+    pub user: String,
+    pub method: BecomeMethod,
+    pub password: Option<String>,
+}
+
+impl Escalation {
+    const SUDO_PROMPT: &'static str = "__ettac_become_prompt__";
+    const SU_PROMPT: &'static str = "Password:";
+    const READY: &'static str = "__ettac_become_ready__";
+
+    /// Once authenticated, the wrapped command prints a marker telling that
+    /// no password prompt will follow
+    fn wrap(&self, cmd: &str) -> Result<String, Error> {
+        let script = quote(&format!("echo {} >&2; {}", Self::READY, cmd))?;
+        let user = quote(&self.user)?;
+
+        Ok(match self.method {
+            BecomeMethod::Sudo => format!(
+                "sudo -S -p {} -u {} -- sh -c {}",
+                Self::SUDO_PROMPT,
+                user,
+                script
+            ),
+            BecomeMethod::Su => format!("su -s /bin/sh -c {} {}", script, user),
+        })
+    }
+
+    fn prompt(&self) -> &'static str {
+        match self.method {
+            BecomeMethod::Sudo => Self::SUDO_PROMPT,
+            BecomeMethod::Su => Self::SU_PROMPT,
+        }
+    }
+}
+
+/// Watches the output of an escalated command for password prompts
+struct Handshake<'a> {
+    escalation: &'a Escalation,
+    prompts: usize,
+    ready: bool,
+    on_stdout: bool,
+}
+
+impl<'a> Handshake<'a> {
+    fn new(escalation: &'a Escalation, on_stdout: bool) -> Self {
+        Self {
+            escalation,
+            prompts: 0,
+            ready: false,
+            on_stdout,
+        }
+    }
+
+    /// Removes the markers from the output and returns the password when it
+    /// has been prompted for
+    fn process(&mut self, output: &mut Vec<u8>) -> Result<Option<&'a str>, AccessError> {
+        if take_marker(output, Escalation::READY) {
+            self.ready = true;
+            return Ok(None);
+        }
+
+        if !take_marker(output, self.escalation.prompt()) {
+            return Ok(None);
+        }
+
+        self.prompts += 1;
+        match (&self.escalation.password, self.prompts) {
+            (None, _) => Err(AccessError::BecomePasswordRequired),
+            (Some(_), 2..) => Err(AccessError::BecomePasswordRejected),
+            (Some(password), _) => Ok(Some(password.as_str())),
+        }
+    }
+}
+
+fn take_marker(output: &mut Vec<u8>, marker: &str) -> bool {
+    let marker = marker.as_bytes();
+    let Some(start) = output
+        .windows(marker.len())
+        .position(|window| window == marker)
+    else {
+        return false;
+    };
+
+    let mut end = start + marker.len();
+    while end < output.len() && matches!(output[end], b'\r' | b'\n' | b' ') {
+        end += 1;
+    }
+
+    output.drain(start..end);
+    true
+}
+
+fn run_local(mut command: Command, deadline: Option<Instant>) -> Result<CommandResult, Error> {
+    let child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let watchdog = Watchdog::start(child.id(), deadline);
+    let output = child.wait_with_output();
+    watchdog.stop()?;
+
+    Ok(output?.into())
+}
+
+fn run_local_escalated(
+    mut command: Command,
+    escalation: &Escalation,
+    deadline: Option<Instant>,
+) -> Result<CommandResult, Error> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+
+    let watchdog = Watchdog::start(child.id(), deadline);
+
+    let mut stdin = child.stdin.take();
+    let mut child_stdout = child.stdout.take().expect("stdout is piped");
+    let mut child_stderr = child.stderr.take().expect("stderr is piped");
+
+    let stdout_reader = thread::spawn(move || {
+        let mut stdout = Vec::new();
+        child_stdout.read_to_end(&mut stdout).map(|_| stdout)
+    });
+
+    let mut handshake = Handshake::new(escalation, false);
+    let mut stderr = Vec::new();
+    let mut buffer = [0; 8192];
+
+    loop {
+        let read = child_stderr.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+
+        stderr.extend_from_slice(&buffer[..read]);
+        if handshake.ready {
+            continue;
+        }
+
+        match handshake.process(&mut stderr) {
+            Ok(Some(password)) => {
+                if let Some(stdin) = stdin.as_mut() {
+                    writeln!(stdin, "{}", password)?;
+                }
+            }
+            Ok(None) if handshake.ready => drop(stdin.take()),
+            Ok(None) => {}
+            Err(err) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                err?
+            }
+        }
+    }
+
+    let status = child.wait()?;
+    let stdout = stdout_reader.join().expect("stdout reader panicked")?;
+    watchdog.stop()?;
+
+    Ok(CommandResult {
+        status: status.code().unwrap_or(UNKNOWN_STATUS),
+        stdout: String::from_utf8_lossy(&stdout).to_string(),
+        stderr: String::from_utf8_lossy(&stderr).to_string(),
+    })
+}
+
+/// Kills the process group of a local command once its deadline is reached or
+/// the deploy is interrupted
+struct Watchdog {
+    stop: mpsc::Sender<()>,
+    thread: JoinHandle<Option<Kill>>,
+    _child: interrupt::Child,
+}
+
+/// Reason a command was killed for
+enum Kill {
+    Deadline,
+    Interrupt,
+}
+
+impl Watchdog {
+    const INTERVAL: Duration = Duration::from_millis(100);
+
+    fn start(pid: u32, deadline: Option<Instant>) -> Self {
+        let (stop, stopped) = mpsc::channel::<()>();
+
+        let thread = thread::spawn(move || {
+            loop {
+                let kill = if interrupt::requested() {
+                    Some(Kill::Interrupt)
+                } else if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                    Some(Kill::Deadline)
+                } else {
+                    None
+                };
+
+                if kill.is_some() {
+                    //the group was created with the command so its id is the pid
+                    unsafe { libc::kill(-(pid as libc::pid_t), libc::SIGKILL) };
+                    return kill;
+                }
+
+                let wait = match deadline {
+                    Some(deadline) => deadline
+                        .saturating_duration_since(Instant::now())
+                        .min(Self::INTERVAL),
+                    None => Self::INTERVAL,
+                };
+
+                if !matches!(stopped.recv_timeout(wait), Err(RecvTimeoutError::Timeout)) {
+                    return None;
+                }
+            }
+        });
+
+        Watchdog {
+            stop,
+            thread,
+            _child: interrupt::Child::register(pid),
+        }
+    }
+
+    /// Fails when the command had to be killed
+    fn stop(self) -> Result<(), Error> {
+        let _ = self.stop.send(());
+
+        match self.thread.join().unwrap_or(None) {
+            Some(Kill::Deadline) => Err(Error::Timeout),
+            Some(Kill::Interrupt) => Err(Error::Interrupted),
+            None => Ok(()),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -120,29 +676,200 @@ pub struct CommandResult {
     pub stderr: String,
 }
 
+impl From<Output> for CommandResult {
+    fn from(output: Output) -> Self {
+        CommandResult {
+            status: output.status.code().unwrap_or(UNKNOWN_STATUS),
+            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        }
+    }
+}
+
 pub fn to(path: impl Into<String>, cred: &Option<SshCredentials>) -> Result<Access, Error> {
     let path = path.into();
 
     if let Some(cred) = cred {
-        let sess = Session::new()?;
-        sess.set_option(SshOption::Hostname(cred.hostname.clone()))?;
-        sess.set_option(SshOption::Port(cred.port))?;
-        sess.set_option(SshOption::User(Some(cred.user.clone())))?;
-        sess.options_parse_config(None)?;
-        sess.connect()?;
+        let sess = connect(cred)?;
 
-        match &cred.credential {
-            AuthMethod::Password(password) => {
-                sess.userauth_password(None, Some(password))?;
-            }
+        let status = match &cred.credential {
+            AuthMethod::Password(password) => sess.userauth_password(None, Some(password))?,
             AuthMethod::Key(key, passphrase) => {
                 let key = SshKey::from_privkey_base64(key, passphrase.as_deref())?;
-                sess.userauth_publickey(None, &key)?;
+                sess.userauth_publickey(None, &key)?
             }
+            AuthMethod::Auto => sess.userauth_public_key_auto(None, None)?,
         };
 
-        Ok(Access::Remote(path, sess))
+        if status != AuthStatus::Success {
+            Err(Error::Authentication(cred.destination().to_string()))?
+        }
+
+        Ok(Access::Remote(path, sess, cred.keepalive_interval))
     } else {
         Ok(Access::Local(path))
     }
 }
+
+/// Opens the connection, retrying with an exponential backoff when it fails.
+/// Authentication is not retried as a denied login will not fix itself.
+fn connect(cred: &SshCredentials) -> Result<Session, Error> {
+    let attempts = u32::from(cred.connect_retries) + 1;
+    let mut backoff = Duration::from_secs(1);
+
+    for attempt in 1..=attempts {
+        let result = Session::new().and_then(|sess| {
+            sess.set_option(SshOption::Hostname(cred.destination().to_string()))?;
+            sess.set_option(SshOption::Timeout(cred.connect_timeout))?;
+            sess.options_parse_config(None)?;
+
+            //explicit options are set last as the ssh config overrides them
+            if cred.alias.is_some()
+                && let Some(hostname) = &cred.hostname
+            {
+                sess.set_option(SshOption::Hostname(hostname.clone()))?;
+            }
+
+            if let Some(port) = cred.port {
+                sess.set_option(SshOption::Port(port))?;
+            }
+
+            if let Some(user) = &cred.user {
+                sess.set_option(SshOption::User(Some(user.clone())))?;
+            }
+
+            sess.connect()?;
+
+            Ok(sess)
+        });
+
+        match result {
+            Ok(sess) => return Ok(sess),
+            Err(err) if attempt == attempts => {
+                return Err(Error::Connection {
+                    hostname: cred.destination().to_string(),
+                    attempts,
+                    source: err,
+                });
+            }
+            Err(err) => {
+                eprintln!(
+                    "Connection to {} failed (attempt {}/{}): {}, retrying in {}s",
+                    cred.destination(),
+                    attempt,
+                    attempts,
+                    err,
+                    backoff.as_secs(),
+                );
+
+                interrupt::sleep(backoff)?;
+                backoff = (backoff * 2).min(MAX_RETRY_BACKOFF);
+            }
+        }
+    }
+
+    unreachable!("the last attempt always returns")
+}
+
+/// Reads stdout and stderr alternately until the command exits so neither
+/// stream can fill up the channel window and block the other, waiting only
+/// when both are empty. An ignore message is sent every `keepalive` to keep
+/// idle connections open.
+fn read_output(
+    sess: &Session,
+    channel: &Channel,
+    keepalive: Option<Duration>,
+    deadline: Option<Instant>,
+    mut handshake: Option<&mut Handshake>,
+) -> Result<(String, String), Error> {
+    let mut stdout = Vec::new();
+    let mut stderr = Vec::new();
+    let mut buffer = [0; 8192];
+    let mut last_keepalive = Instant::now();
+
+    loop {
+        let read_stdout = channel.read_nonblocking(&mut buffer, false)?;
+        stdout.extend_from_slice(&buffer[..read_stdout]);
+
+        let read_stderr = channel.read_nonblocking(&mut buffer, true)?;
+        stderr.extend_from_slice(&buffer[..read_stderr]);
+
+        if let Some(handshake) = handshake.as_mut()
+            && !handshake.ready
+        {
+            let output = if handshake.on_stdout {
+                &mut stdout
+            } else {
+                &mut stderr
+            };
+
+            match handshake.process(output) {
+                Ok(Some(password)) => {
+                    channel
+                        .stdin()
+                        .write_all(format!("{}\n", password).as_bytes())?;
+                }
+                Ok(None) if handshake.ready => channel.send_eof()?,
+                Ok(None) => {}
+                Err(err) => {
+                    channel.close()?;
+                    err?
+                }
+            }
+        }
+
+        if read_stdout == 0 && read_stderr == 0 {
+            if channel.is_eof() {
+                break;
+            }
+
+            wait_readable(sess, READ_POLL_INTERVAL);
+        }
+
+        let interrupted = interrupt::requested();
+        if interrupted || deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            //servers may not support signals, closing the channel is the
+            //fallback
+            let _ = channel.request_send_signal("KILL");
+            channel.close()?;
+
+            match interrupted {
+                true => Err(Error::Interrupted)?,
+                false => Err(Error::Timeout)?,
+            }
+        }
+
+        if let Some(interval) = keepalive
+            && last_keepalive.elapsed() >= interval
+        {
+            sess.send_ignore(b"\0")?;
+            last_keepalive = Instant::now();
+        }
+    }
+
+    //terminals translate line feeds
+    let on_pty = handshake.is_some_and(|handshake| handshake.on_stdout);
+    let to_string = |output: &[u8]| {
+        let output = String::from_utf8_lossy(output);
+        if on_pty {
+            output.replace("\r\n", "\n")
+        } else {
+            output.to_string()
+        }
+    };
+
+    Ok((to_string(&stdout), to_string(&stderr)))
+}
+
+/// Waits until something is received on the session, the output of either
+/// stream or the end of the command, or until the timeout
+fn wait_readable(sess: &Session, timeout: Duration) {
+    let mut socket = libc::pollfd {
+        fd: sess.as_raw_fd(),
+        events: libc::POLLIN,
+        revents: 0,
+    };
+
+    //errors such as an interrupted call are handled by polling again
+    unsafe { libc::poll(&mut socket, 1, timeout.as_millis() as libc::c_int) };
+}
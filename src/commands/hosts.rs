@@ -0,0 +1,177 @@
+use crate::Error;
+use crate::config::HostsCommand;
+use crate::context::{AuthMethod, Host};
+use crate::runners::Runner;
+use serde_json::{Map, Value, json};
+use std::collections::BTreeMap;
+
+const REDACTED: &str = "<redacted>";
+
+/// Prints the hosts as resolved from `setup()`, once the defaults are applied
+pub fn hosts(runner: &mut impl Runner, command: &HostsCommand) -> Result<(), Error> {
+    let mut hosts = runner
+        .get_hosts()?
+        .into_iter()
+        .collect::<Vec<(String, Host)>>();
+    hosts.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    if command.json {
+        let hosts = hosts
+            .iter()
+            .map(|(name, host)| (name.clone(), to_json(host, command.show_secrets)))
+            .collect::<Map<String, Value>>();
+
+        println!("{:#}", Value::Object(hosts));
+    } else {
+        print_table(&hosts);
+    }
+
+    Ok(())
+}
+
+/// Secrets are replaced, only the kind of credential is kept, and the values
+/// of `env` and `vars` are only printed with `--show-secrets` as they often
+/// hold tokens
+fn to_json(host: &Host, show_secrets: bool) -> Value {
+    let values = |values: &BTreeMap<String, String>| {
+        values
+            .iter()
+            .map(|(key, value)| {
+                let value = if show_secrets {
+                    value.as_str()
+                } else {
+                    REDACTED
+                };
+                (key.clone(), json!(value))
+            })
+            .collect::<Map<String, Value>>()
+    };
+
+    let ssh = host.ssh.as_ref().map(|ssh| {
+        let credential = match &ssh.credential {
+            AuthMethod::Password(_) => json!({ "password": REDACTED }),
+            AuthMethod::Key(_, passphrase) => json!({
+                "key": REDACTED,
+                "passphrase": passphrase.as_ref().map(|_| REDACTED),
+            }),
+            AuthMethod::Auto => json!("ssh config"),
+        };
+
+        json!({
+            "alias": ssh.alias,
+            "hostname": ssh.hostname,
+            "port": ssh.port,
+            "user": ssh.user,
+            "credential": credential,
+            "connect_timeout": ssh.connect_timeout.as_secs(),
+            "keepalive_interval": ssh.keepalive_interval.map(|interval| interval.as_secs()),
+            "connect_retries": ssh.connect_retries,
+        })
+    });
+
+    json!({
+        "path": host.path,
+        "stage": host.stage,
+        "repository": host.repository,
+        "labels": host.labels,
+        "keep_releases": host.keep_releases,
+        "persistent_files": host.persistent_files,
+        "persistent_dirs": host.persistent_dirs,
+        "env": values(&host.env),
+        "vars": values(&host.vars),
+        "ssh": ssh,
+        "become": host.become_user,
+        "become_method": host.become_method.to_string(),
+        "become_password": host.become_password.as_ref().map(|_| REDACTED),
+        "strategy": host.strategy.to_string(),
+        "batch_size": host.batch_size.to_string(),
+        "batch_pause": host.batch_pause.as_secs(),
+        "rollback_on_failure": host.rollback_on_failure,
+        "auto_rollback": host.auto_rollback,
+    })
+}
+
+fn print_table(hosts: &[(String, Host)]) {
+    let header = [
+        "HOST",
+        "STAGE",
+        "TARGET",
+        "PATH",
+        "LABELS",
+        "KEEP RELEASES",
+        "PERSISTENT FILES",
+        "PERSISTENT DIRS",
+    ];
+
+    let rows = hosts
+        .iter()
+        .map(|(name, host)| {
+            [
+                name.clone(),
+                host.stage.clone().unwrap_or_else(|| String::from("-")),
+                target(host),
+                host.path.clone(),
+                list(&host.labels),
+                host.keep_releases.to_string(),
+                list(&host.persistent_files),
+                list(&host.persistent_dirs),
+            ]
+        })
+        .collect::<Vec<[String; 8]>>();
+
+    let mut widths = header.map(str::len);
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let print_row = |cells: &[&str]| {
+        let line = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:width$}", cell, width = width))
+            .collect::<Vec<String>>()
+            .join("  ");
+
+        println!("{}", line.trim_end());
+    };
+
+    print_row(&header);
+    for row in &rows {
+        print_row(&row.each_ref().map(String::as_str));
+    }
+}
+
+fn target(host: &Host) -> String {
+    match &host.ssh {
+        Some(ssh) => {
+            let auth = match ssh.credential {
+                AuthMethod::Password(_) => "password",
+                AuthMethod::Key(..) => "key",
+                AuthMethod::Auto => "ssh config",
+            };
+
+            let user = match &ssh.user {
+                Some(user) => format!("{}@", user),
+                None => String::new(),
+            };
+
+            let port = match ssh.port {
+                Some(port) => format!(":{}", port),
+                None => String::new(),
+            };
+
+            format!("{}{}{} ({})", user, ssh.destination(), port, auth)
+        }
+        None => String::from("local"),
+    }
+}
+
+fn list(values: &[String]) -> String {
+    if values.is_empty() {
+        String::from("-")
+    } else {
+        values.join(",")
+    }
+}
@@ -0,0 +1,9 @@
+mod hosts;
+mod resume;
+mod run_task;
+mod tasks;
+
+pub use hosts::hosts;
+pub use resume::resume;
+pub use run_task::run_task;
+pub use tasks::tasks;
@@ -0,0 +1,25 @@
+use crate::Error;
+use crate::config::{Config, ResumeCommand};
+use crate::deployment;
+use crate::progress::Progress;
+use crate::runners::Runner;
+
+/// Continues the failed deploy of a host from the progress it left
+pub fn resume(
+    runner: &mut impl Runner,
+    command: &ResumeCommand,
+    config: &Config,
+) -> Result<(), Error> {
+    let mut hosts = runner.get_hosts()?;
+    let Some(host) = hosts.remove(&command.host) else {
+        return Err(Error::UnknownHosts(vec![command.host.clone()]));
+    };
+
+    let progress = Progress::load(config, &command.host)?;
+    println!(
+        "Resuming the deploy of host {} in release {}",
+        command.host, progress.release
+    );
+
+    deployment::resume(runner, command.host.clone(), host, config, progress)
+}
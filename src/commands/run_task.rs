@@ -0,0 +1,112 @@
+use crate::Error;
+use crate::access::{self, Access};
+use crate::config::RunTaskCommand;
+use crate::context::Context;
+use crate::interrupt;
+use crate::runners::Runner;
+use crate::tasks::RunOnce;
+use std::rc::Rc;
+
+/// Directory created by `System:lock()` while a deploy runs
+const LOCK: &str = ".ettac.lock";
+
+/// Stands for the release during a dry run, the `current` link is not read
+const PLANNED_RELEASE: &str = "<current>";
+
+/// Runs one task of a host, in the release `current` points to
+pub fn run_task(
+    runner: &mut impl Runner,
+    command: &RunTaskCommand,
+    dry_run: bool,
+) -> Result<(), Error> {
+    let mut hosts = runner.get_hosts()?;
+    let Some(host) = hosts.remove(&command.host) else {
+        return Err(Error::UnknownHosts(vec![command.host.clone()]));
+    };
+
+    let graph = runner.describe(&host)?;
+    let task = graph.find(&command.task)?;
+
+    let mut tasks = match command.with_dependencies {
+        true => graph.dependencies(task),
+        false => Vec::new(),
+    };
+    tasks.push(task);
+
+    //installed before taking the lock so a signal cannot leave it behind
+    interrupt::install();
+
+    let (access, release) = if dry_run {
+        let access = Access::DryRun(host.path.clone());
+        (access, String::from(PLANNED_RELEASE))
+    } else {
+        let access = access::to(&host.path, &host.ssh)?;
+
+        //the lock of the deploys keeps one from switching `current` while
+        //the task runs in it
+        lock(&access, &command.host, &host.path)?;
+        let Some(release) = current_release(&access) else {
+            interrupt::handled();
+            unlock(&access)?;
+            return Err(Error::NoCurrentRelease(command.host.clone()));
+        };
+
+        (access, release)
+    };
+
+    println!(
+        "Running {} on host {} in release {}",
+        tasks
+            .iter()
+            .map(|task| task.name.as_str())
+            .collect::<Vec<&str>>()
+            .join(", "),
+        command.host,
+        release
+    );
+
+    let mut ctx = Context::new(host, access, release, Rc::new(RunOnce::default()));
+    let result = graph.run_selected(&mut ctx, &tasks);
+
+    if !dry_run {
+        //the lock is released even when the task was interrupted
+        interrupt::handled();
+        unlock(&ctx.access)?;
+    }
+
+    result.map_err(|err| Error::Host(command.host.clone(), Box::new(err)))
+}
+
+/// Takes the lock of the deploys, `mkdir` fails when it is already taken
+fn lock(access: &Access, host: &str, path: &str) -> Result<(), Error> {
+    let result = access.run(&format!("mkdir {}", LOCK))?;
+    if result.status != 0 {
+        Err(Error::DeployRunning {
+            host: host.to_string(),
+            path: path.to_string(),
+        })?
+    }
+
+    Ok(())
+}
+
+fn unlock(access: &Access) -> Result<(), Error> {
+    access.run(&format!("rm -rf {}", LOCK))?;
+    Ok(())
+}
+
+/// Name of the release the `current` link of the host points to
+fn current_release(access: &Access) -> Option<String> {
+    let result = access.run("readlink current").ok()?;
+    if result.status != 0 {
+        return None;
+    }
+
+    result
+        .stdout
+        .trim_end()
+        .rsplit('/')
+        .next()
+        .filter(|release| !release.is_empty())
+        .map(str::to_string)
+}
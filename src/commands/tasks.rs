@@ -0,0 +1,204 @@
+use crate::Error;
+use crate::config::{TasksCommand, TasksFormat};
+use crate::runners::Runner;
+use crate::tasks::{Phase, Task};
+
+/// Prints the tasks of a host in the order they run, once `use()`, `after()`,
+/// `remove()` and `wrap()` have been applied by `describe()`
+pub fn tasks(runner: &mut impl Runner, command: &TasksCommand) -> Result<(), Error> {
+    let mut hosts = runner.get_hosts()?;
+    let Some(host) = hosts.remove(&command.host) else {
+        return Err(Error::UnknownHosts(vec![command.host.clone()]));
+    };
+
+    let graph = runner.describe(&host)?;
+    let tasks = graph.ordered();
+
+    match command.format {
+        TasksFormat::Tree => print_tree(&command.host, &tasks),
+        TasksFormat::Dot => print_dot(&tasks),
+        TasksFormat::Mermaid => print_mermaid(&tasks),
+    }
+
+    Ok(())
+}
+
+fn print_tree(host: &str, tasks: &[&Task]) {
+    println!("{}", host);
+
+    let phases = tasks
+        .chunk_by(|a, b| a.options.phase == b.options.phase)
+        .collect::<Vec<&[&Task]>>();
+
+    for (index, phase_tasks) in phases.iter().enumerate() {
+        let last_phase = index == phases.len() - 1;
+        let (branch, indent) = match last_phase {
+            true => ("└── ", "    "),
+            false => ("├── ", "│   "),
+        };
+
+        println!("{}{}", branch, phase_tasks[0].options.phase);
+
+        for (index, task) in phase_tasks.iter().enumerate() {
+            let branch = match index == phase_tasks.len() - 1 {
+                true => "└── ",
+                false => "├── ",
+            };
+
+            let options = options(task);
+            let options = match options.is_empty() {
+                true => String::new(),
+                false => format!(" [{}]", options.join(", ")),
+            };
+
+            println!(
+                "{}{}{} ({}){}",
+                indent,
+                branch,
+                task.name,
+                origin(task),
+                options
+            );
+        }
+    }
+}
+
+fn print_dot(tasks: &[&Task]) {
+    println!("digraph tasks {{");
+    println!("    node [shape=box];");
+
+    for phase_tasks in tasks.chunk_by(|a, b| a.options.phase == b.options.phase) {
+        let phase = phase_tasks[0].options.phase;
+
+        println!();
+        println!("    subgraph cluster_{} {{", phase);
+        println!("        label=\"{}\";", phase);
+
+        for task in phase_tasks {
+            let style = match task.builtin {
+                true => ", style=dashed",
+                false => "",
+            };
+
+            println!(
+                "        {} [label=\"{}\\n{}\"{}];",
+                node_id(tasks, task),
+                escape_dot(&task.name),
+                escape_dot(&origin(task)),
+                style
+            );
+        }
+
+        println!("    }}");
+    }
+
+    println!();
+    for (from, to) in edges(tasks) {
+        println!("    {} -> {};", node_id(tasks, from), node_id(tasks, to));
+    }
+
+    println!("}}");
+}
+
+fn print_mermaid(tasks: &[&Task]) {
+    println!("flowchart TD");
+
+    for phase_tasks in tasks.chunk_by(|a, b| a.options.phase == b.options.phase) {
+        let phase = phase_tasks[0].options.phase;
+        println!("    subgraph phase_{} [{}]", phase, phase);
+
+        for task in phase_tasks {
+            let class = match task.builtin {
+                true => ":::builtin",
+                false => "",
+            };
+
+            println!(
+                "        {}[\"{}<br/>{}\"]{}",
+                node_id(tasks, task),
+                escape_mermaid(&task.name),
+                escape_mermaid(&origin(task)),
+                class
+            );
+        }
+
+        println!("    end");
+    }
+
+    for (from, to) in edges(tasks) {
+        println!("    {} --> {}", node_id(tasks, from), node_id(tasks, to));
+    }
+
+    println!("    classDef builtin stroke-dasharray: 5 5");
+}
+
+/// Tasks that run one after the other, the failure and rollback tasks only
+/// run when something went wrong so they are chained on their own
+fn edges<'a>(tasks: &[&'a Task]) -> Vec<(&'a Task, &'a Task)> {
+    let chain = |task: &Task| match task.options.phase {
+        Phase::Failure => 1,
+        Phase::Rollback => 2,
+        _ => 0,
+    };
+
+    tasks
+        .chunk_by(|a, b| chain(a) == chain(b))
+        .flat_map(|chain| chain.windows(2).map(|pair| (pair[0], pair[1])))
+        .collect()
+}
+
+/// Task names are not unique across modules so nodes are named by position
+fn node_id(tasks: &[&Task], task: &Task) -> String {
+    let index = tasks
+        .iter()
+        .position(|candidate| std::ptr::eq(*candidate, task))
+        .expect("task is part of the graph");
+
+    format!("task{}", index)
+}
+
+fn origin(task: &Task) -> String {
+    match task.builtin {
+        true => format!("{}, built-in", task.origin),
+        false => task.origin.clone(),
+    }
+}
+
+fn options(task: &Task) -> Vec<String> {
+    let options = &task.options;
+    let mut descriptions = Vec::new();
+
+    if !options.on.is_empty() {
+        descriptions.push(format!("on {}", options.on.join(",")));
+    }
+
+    if options.once {
+        descriptions.push(String::from("once"));
+    }
+
+    if options.when.is_some() {
+        descriptions.push(String::from("when"));
+    }
+
+    if options.rerun {
+        descriptions.push(String::from("rerun"));
+    }
+
+    if options.retries > 0 {
+        descriptions.push(format!("retries {}", options.retries));
+    }
+
+    if let Some(timeout) = options.timeout {
+        descriptions.push(format!("timeout {}s", timeout.as_secs()));
+    }
+
+    descriptions
+}
+
+fn escape_dot(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_mermaid(value: &str) -> String {
+    value.replace('"', "#quot;")
+}
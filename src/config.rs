@@ -1,8 +1,12 @@
 use argh::FromArgs;
+use std::str::FromStr;
 
 #[derive(FromArgs, Debug)]
 /// Runs an ettac script
 pub struct Config {
+    #[argh(subcommand)]
+    pub command: Option<Command>,
+
     #[argh(positional)]
     /// host(s) to deploy to
     pub hosts: Vec<String>,
@@ -10,4 +14,120 @@ pub struct Config {
     #[argh(option, short = 's', default = "String::from(\"deploy.lua\")")]
     /// path of the script to run
     pub script: String,
+
+    #[argh(option, short = 'o')]
+    /// override an option of the hosts, e.g. `-o keep_releases=10` or
+    /// `-o vars.branch=hotfix`, can be repeated
+    pub overrides: Vec<String>,
+
+    #[argh(switch)]
+    /// print the commands, uploads and symlinks of the deploy without running
+    /// them, the commands of `inventory_command()` still run to list the hosts
+    pub dry_run: bool,
+
+    #[argh(switch)]
+    /// do not run the commands of `inventory_command()`, the hosts they list
+    /// are left out
+    pub no_inventory_commands: bool,
+
+    #[argh(switch)]
+    /// keep deploying the other hosts when a host fails
+    pub keep_going: bool,
+
+    #[argh(switch)]
+    /// keep the release of an interrupted deploy so it can be resumed
+    pub keep_release: bool,
+
+    #[argh(option)]
+    /// task not to run, named `name` or `Module.name`, can be repeated
+    pub skip: Vec<String>,
+
+    #[argh(option)]
+    /// task to run along with the built-in ones, the other tasks are skipped,
+    /// can be repeated
+    pub only: Vec<String>,
+}
+
+#[derive(FromArgs, Debug)]
+#[argh(subcommand)]
+pub enum Command {
+    Hosts(HostsCommand),
+    Tasks(TasksCommand),
+    RunTask(RunTaskCommand),
+    Resume(ResumeCommand),
+}
+
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "hosts")]
+/// List the hosts with their effective configuration
+pub struct HostsCommand {
+    #[argh(switch)]
+    /// print the hosts as JSON instead of a table
+    pub json: bool,
+
+    #[argh(switch)]
+    /// print the values of `env` and `vars` in the JSON instead of redacting them
+    pub show_secrets: bool,
+}
+
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "tasks")]
+/// Show the tasks of a host in the order they run, without connecting to it
+pub struct TasksCommand {
+    #[argh(positional)]
+    /// host whose recipe is described
+    pub host: String,
+
+    #[argh(option, default = "TasksFormat::Tree")]
+    /// output format, `tree`, `dot` or `mermaid`
+    pub format: TasksFormat,
+}
+
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "run-task")]
+/// Run a single task of a host in its current release
+pub struct RunTaskCommand {
+    #[argh(positional)]
+    /// host to run the task on
+    pub host: String,
+
+    #[argh(positional)]
+    /// task to run, named `name` or `Module.name`
+    pub task: String,
+
+    #[argh(switch)]
+    /// also run the tasks it was placed after with `after()`
+    pub with_dependencies: bool,
+}
+
+#[derive(FromArgs, Debug)]
+#[argh(subcommand, name = "resume")]
+/// Continue the failed deploy of a host from the task that failed
+pub struct ResumeCommand {
+    #[argh(positional)]
+    /// host whose deploy failed
+    pub host: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TasksFormat {
+    Tree,
+    Dot,
+    Mermaid,
+}
+
+impl FromStr for TasksFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "tree" => Ok(TasksFormat::Tree),
+            "dot" => Ok(TasksFormat::Dot),
+            "mermaid" => Ok(TasksFormat::Mermaid),
+            format => Err(format!(
+                "unknown format `{}`, expected `tree`, `dot` or `mermaid`",
+                format
+            )),
+        }
+    }
 }
@@ -1,14 +1,104 @@
 use crate::Error;
 use crate::access::Access;
 use crate::error::SetupError;
+use crate::plan::Plan;
+use crate::progress::Progress;
+use crate::tasks::{RunOnce, TaskFilter, TaskOptions};
 use partially::Partial;
-use std::fmt::Debug;
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::fmt::{Debug, Display, Formatter};
 use std::rc::Rc;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 #[derive(Debug)]
 pub struct Context {
     pub host: Host,
     pub access: Access,
+    pub release: String,
+    pub task: TaskOptions,
+    pub become_password: RefCell<Option<String>>,
+    pub plan: Option<Plan>,
+    pub run_once: Rc<RunOnce>,
+    /// Point after which the commands of the current task are stopped
+    pub deadline: Cell<Option<Instant>>,
+    pub filter: TaskFilter,
+    /// Tasks the host went through, kept to resume the deploy if it fails
+    pub progress: Option<Progress>,
+    /// Set when the deploy is interrupted, the failure tasks then remove the
+    /// release instead of keeping it to be resumed
+    pub discard_release: bool,
+}
+
+impl Context {
+    pub fn new(
+        host: Host,
+        access: Access,
+        release: impl Into<String>,
+        run_once: Rc<RunOnce>,
+    ) -> Self {
+        let become_password = RefCell::new(host.become_password.clone());
+        let plan = matches!(access, Access::DryRun(_)).then(Plan::default);
+
+        Self {
+            host,
+            access,
+            release: release.into(),
+            task: TaskOptions::default(),
+            become_password,
+            plan,
+            run_once,
+            deadline: Cell::new(None),
+            filter: TaskFilter::default(),
+            progress: None,
+            discard_release: false,
+        }
+    }
+
+    pub fn release_path(&self) -> String {
+        format!("{}/releases/{}", self.host.path, self.release)
+    }
+
+    pub fn shared_path(&self) -> String {
+        format!("{}/shared", self.host.path)
+    }
+
+    pub fn current_path(&self) -> String {
+        format!("{}/current", self.host.path)
+    }
+}
+
+/// Names releases after the current UTC time so they sort chronologically
+pub fn new_release_name() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+
+    //civil from days algorithm from http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}{:02}{:02}{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day % 3600 / 60,
+        secs_of_day % 60,
+    )
 }
 
 #[derive(Partial, Debug)]
@@ -20,10 +110,28 @@ pub struct Host {
     pub persistent_files: Vec<String>,
     pub persistent_dirs: Vec<String>,
     pub labels: Vec<String>,
+    pub env: BTreeMap<String, String>,
+    pub vars: BTreeMap<String, String>,
 
     #[partially(as_type = "Option<PartialSshCredentials>")]
     pub ssh: Option<SshCredentials>,
     pub path: String,
+    #[partially(as_type = "Option<String>")]
+    pub stage: Option<String>,
+
+    /// Set to [`Become::Disabled`] by `become = false`, which overrides the
+    /// user of the defaults
+    #[partially(as_type = "Option<Become>")]
+    pub become_user: Option<String>,
+    pub become_method: BecomeMethod,
+    #[partially(as_type = "Option<String>")]
+    pub become_password: Option<String>,
+
+    pub strategy: Strategy,
+    pub batch_size: BatchSize,
+    pub batch_pause: Duration,
+    pub rollback_on_failure: bool,
+    pub auto_rollback: bool,
 }
 
 impl TryFrom<PartialHost> for Host {
@@ -37,8 +145,23 @@ impl TryFrom<PartialHost> for Host {
             persistent_files: value.persistent_files.unwrap_or_default(),
             persistent_dirs: value.persistent_dirs.unwrap_or_default(),
             labels: value.labels.unwrap_or_default(),
-            ssh: value.ssh.map(SshCredentials::try_from).transpose()?,
+            env: value.env.unwrap_or_default(),
+            vars: value.vars.unwrap_or_default(),
+            ssh: value
+                .ssh
+                .filter(|ssh| !ssh.is_local())
+                .map(SshCredentials::try_from)
+                .transpose()?,
             path: value.path.ok_or(SetupError::MissingPath)?,
+            stage: value.stage,
+            become_user: value.become_user.and_then(Option::from),
+            become_method: value.become_method.unwrap_or_default(),
+            become_password: value.become_password,
+            strategy: value.strategy.unwrap_or_default(),
+            batch_size: value.batch_size.unwrap_or_default(),
+            batch_pause: value.batch_pause.unwrap_or_default(),
+            rollback_on_failure: value.rollback_on_failure.unwrap_or_default(),
+            auto_rollback: value.auto_rollback.unwrap_or_default(),
         })
     }
 }
@@ -46,15 +169,47 @@ impl TryFrom<PartialHost> for Host {
 #[derive(Partial, Debug)]
 #[partially(derive(Clone, Default, Debug))]
 pub struct SshCredentials {
-    pub hostname: String,
-    pub port: u16,
-    pub user: String,
+    #[partially(as_type = "Option<String>")]
+    pub alias: Option<String>,
+    #[partially(as_type = "Option<String>")]
+    pub hostname: Option<String>,
+    #[partially(as_type = "Option<u16>")]
+    pub port: Option<u16>,
+    #[partially(as_type = "Option<String>")]
+    pub user: Option<String>,
     pub credential: AuthMethod,
+    pub connect_timeout: Duration,
+    #[partially(as_type = "Option<Duration>")]
+    pub keepalive_interval: Option<Duration>,
+    pub connect_retries: u8,
+}
+
+impl SshCredentials {
+    pub const DEFAULT_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+    pub const DEFAULT_CONNECT_RETRIES: u8 = 2;
+
+    /// Name the host is reached by, the alias is resolved by the ssh config
+    pub fn destination(&self) -> &str {
+        self.alias
+            .as_deref()
+            .or(self.hostname.as_deref())
+            .expect("credentials have an alias or a hostname")
+    }
 }
 
 impl PartialSshCredentials {
     pub fn is_empty(&self) -> bool {
-        self.hostname.is_none()
+        self.is_local()
+            && self.connect_timeout.is_none()
+            && self.keepalive_interval.is_none()
+            && self.connect_retries.is_none()
+    }
+
+    /// Connection tuning options can be set in `default()` without turning
+    /// local hosts into remote ones, so they are not taken into account
+    pub fn is_local(&self) -> bool {
+        self.alias.is_none()
+            && self.hostname.is_none()
             && self.port.is_none()
             && self.user.is_none()
             && self.credential.is_none()
@@ -73,23 +228,39 @@ impl TryFrom<PartialSshCredentials> for SshCredentials {
 
     fn try_from(value: PartialSshCredentials) -> Result<Self, Self::Error> {
         let PartialSshCredentials {
+            alias,
             hostname,
             port,
             user,
             credential,
+            connect_timeout,
+            keepalive_interval,
+            connect_retries,
         } = value;
 
-        match (hostname, user, credential) {
-            (Some(hostname), Some(user), Some(credential)) => Ok(SshCredentials {
-                hostname,
-                port: port.unwrap_or(22),
-                user,
-                credential,
-            }),
-            (hostname, user, credential) => {
+        //an alias lets the ssh config provide the hostname, user and keys
+        let credential = match (&alias, credential) {
+            (Some(_), None) => Some(AuthMethod::Auto),
+            (_, credential) => credential,
+        };
+
+        match (&alias, &hostname, &user, credential) {
+            (Some(_), _, _, Some(credential)) | (None, Some(_), Some(_), Some(credential)) => {
+                Ok(SshCredentials {
+                    alias,
+                    hostname,
+                    port,
+                    user,
+                    credential,
+                    connect_timeout: connect_timeout.unwrap_or(Self::DEFAULT_CONNECT_TIMEOUT),
+                    keepalive_interval,
+                    connect_retries: connect_retries.unwrap_or(Self::DEFAULT_CONNECT_RETRIES),
+                })
+            }
+            (_, hostname, user, credential) => {
                 let mut missing = Vec::with_capacity(3);
                 if hostname.is_none() {
-                    missing.push("hostname");
+                    missing.push("hostname or ssh_alias");
                 }
 
                 if user.is_none() {
@@ -110,8 +281,165 @@ impl TryFrom<PartialSshCredentials> for SshCredentials {
 pub enum AuthMethod {
     Password(String),
     Key(String, Option<String>),
+    /// Identity files of the ssh config and keys of the agent
+    Auto,
+}
+
+/// User to run commands as, set on a task or a command. The most specific
+/// level that does not `Inherit` wins over the host's `become` option.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Become {
+    #[default]
+    Inherit,
+    Disabled,
+    User(String),
+}
+
+impl Become {
+    pub fn or(self, other: Become) -> Become {
+        match self {
+            Become::Inherit => other,
+            become_user => become_user,
+        }
+    }
+}
+
+impl From<Become> for Option<String> {
+    fn from(value: Become) -> Self {
+        match value {
+            Become::User(user) => Some(user),
+            Become::Inherit | Become::Disabled => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BecomeMethod {
+    #[default]
+    Sudo,
+    /// `su` only reads the password from a terminal, one is allocated on
+    /// remote hosts but local commands run without any so it is refused on
+    /// local hosts, `sudo` works on both
+    Su,
+}
+
+impl FromStr for BecomeMethod {
+    type Err = SetupError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "sudo" => Ok(BecomeMethod::Sudo),
+            "su" => Ok(BecomeMethod::Su),
+            method => Err(SetupError::InvalidBecomeMethod(method.to_string())),
+        }
+    }
+}
+
+impl Display for BecomeMethod {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BecomeMethod::Sudo => write!(f, "sudo"),
+            BecomeMethod::Su => write!(f, "su"),
+        }
+    }
+}
+
+/// How the selected hosts are split into batches, each batch is deployed
+/// before the next one starts
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Strategy {
+    /// Every host in a single batch
+    #[default]
+    All,
+    /// A first host on its own, then every other host
+    Canary,
+    /// Batches of `batch_size` hosts
+    Rolling,
+}
+
+impl FromStr for Strategy {
+    type Err = SetupError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "all" => Ok(Strategy::All),
+            "canary" => Ok(Strategy::Canary),
+            "rolling" => Ok(Strategy::Rolling),
+            strategy => Err(SetupError::InvalidStrategy(strategy.to_string())),
+        }
+    }
+}
+
+impl Display for Strategy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Strategy::All => write!(f, "all"),
+            Strategy::Canary => write!(f, "canary"),
+            Strategy::Rolling => write!(f, "rolling"),
+        }
+    }
+}
+
+/// Size of the batches of a rolling deploy, either a number of hosts or a
+/// percentage of the selected hosts such as `25%`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BatchSize {
+    Hosts(usize),
+    Percent(u8),
+}
+
+impl Default for BatchSize {
+    fn default() -> Self {
+        BatchSize::Hosts(1)
+    }
+}
+
+impl BatchSize {
+    /// Number of hosts per batch, batches always have at least one host
+    pub fn of(self, hosts: usize) -> usize {
+        let size = match self {
+            BatchSize::Hosts(size) => size,
+            BatchSize::Percent(percent) => (hosts * percent as usize).div_ceil(100),
+        };
+
+        size.max(1)
+    }
+}
+
+impl FromStr for BatchSize {
+    type Err = SetupError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || SetupError::InvalidBatchSize(value.to_string());
+
+        match value.trim().strip_suffix('%') {
+            Some(percent) => match percent.trim().parse::<u8>() {
+                Ok(percent @ 1..=100) => Ok(BatchSize::Percent(percent)),
+                _ => Err(invalid()),
+            },
+            None => match value.trim().parse::<usize>() {
+                Ok(0) | Err(_) => Err(invalid()),
+                Ok(size) => Ok(BatchSize::Hosts(size)),
+            },
+        }
+    }
+}
+
+impl Display for BatchSize {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BatchSize::Hosts(size) => write!(f, "{}", size),
+            BatchSize::Percent(percent) => write!(f, "{}%", percent),
+        }
+    }
 }
 
 pub trait Callable: Debug {
-    fn call(&self) -> Result<(), Error>;
+    fn call(&self, ctx: &Context) -> Result<(), Error>;
+    fn as_any(&self) -> &dyn Any;
+}
+
+/// Decides whether a task runs on a host, see the `when` task option
+pub trait Condition: Debug {
+    fn test(&self, ctx: &Context) -> Result<bool, Error>;
 }
@@ -0,0 +1,553 @@
+use crate::access;
+use crate::access::Access;
+use crate::config::Config;
+use crate::context::{BatchSize, Context, Host, Strategy, new_release_name};
+use crate::error::{Error, SetupError};
+use crate::interrupt;
+use crate::progress::{self, Progress};
+use crate::runners::Runner;
+use crate::tasks::{Phase, RunOnce, TaskFilter, TaskGraph};
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+/// Steps every host of a batch goes through before the next step starts
+const STEPS: [(&str, RangeInclusive<Phase>); 3] = [
+    ("Preparing", Phase::Setup..=Phase::Build),
+    ("Switching", Phase::Switch..=Phase::Switch),
+    ("Finalizing", Phase::Verify..=Phase::Finalize),
+];
+
+/// Host being deployed with the tasks described by its recipe
+struct Target {
+    name: String,
+    ctx: Context,
+    graph: TaskGraph,
+    started_at: Instant,
+    finished_at: Option<Instant>,
+    started: bool,
+    switched: bool,
+    completed: bool,
+    aborted: bool,
+    rolled_back: bool,
+    failed_task: Option<String>,
+}
+
+impl Target {
+    fn run_phases(&mut self, phases: RangeInclusive<Phase>) -> Result<(), Error> {
+        self.graph.run_phases(&mut self.ctx, phases).map_err(|err| {
+            //a task stopped by an interrupt did not fail
+            if let Error::Task(task, _) = &err
+                && !interrupt::requested()
+            {
+                self.failed_task = Some(task.clone());
+            }
+
+            Error::Host(self.name.clone(), Box::new(err))
+        })
+    }
+
+    /// Puts the previous release back with the tasks of the rollback phase,
+    /// the deploy can not be resumed afterwards as its switch was undone
+    fn rollback(&mut self) -> Result<(), Error> {
+        println!("Rolling back host {}", self.name);
+
+        self.graph
+            .run_phases(&mut self.ctx, Phase::Rollback..=Phase::Rollback)
+            .map_err(|err| Error::Host(self.name.clone(), Box::new(err)))?;
+
+        self.rolled_back = true;
+
+        if let Some(progress) = self.ctx.progress.take()
+            && self.ctx.plan.is_none()
+        {
+            progress.discard()?;
+        }
+
+        Ok(())
+    }
+
+    fn outcome(&self) -> &'static str {
+        match (self.completed, self.failed_task.is_some(), self.rolled_back) {
+            (true, _, false) => "deployed",
+            (true, _, true) => "rolled back",
+            (false, true, false) => "failed",
+            (false, true, true) => "failed, rolled back",
+            (false, false, _) if !self.started => "skipped",
+            (false, false, false) => "aborted",
+            (false, false, true) => "aborted, rolled back",
+        }
+    }
+}
+
+/// Rollout settings of the hosts deployed together
+#[derive(Debug, PartialEq)]
+struct Rollout {
+    strategy: Strategy,
+    batch_size: BatchSize,
+    batch_pause: Duration,
+    rollback_on_failure: bool,
+}
+
+impl Rollout {
+    fn of(hosts: &[(String, Host)]) -> Result<Self, Error> {
+        let mut rollouts = hosts.iter().map(|(name, host)| {
+            let rollout = Rollout {
+                strategy: host.strategy,
+                batch_size: host.batch_size,
+                batch_pause: host.batch_pause,
+                rollback_on_failure: host.rollback_on_failure,
+            };
+
+            (name, rollout)
+        });
+
+        let Some((first_name, first)) = rollouts.next() else {
+            return Ok(Rollout::default());
+        };
+
+        for (name, rollout) in rollouts {
+            if rollout != first {
+                return Err(SetupError::MixedRollout(first_name.clone(), name.clone()).into());
+            }
+        }
+
+        Ok(first)
+    }
+
+    /// Number of hosts of each batch
+    fn batches(&self, hosts: usize) -> Vec<usize> {
+        let size = match self.strategy {
+            Strategy::All => hosts,
+            Strategy::Canary => 1,
+            Strategy::Rolling => self.batch_size.of(hosts),
+        };
+
+        let mut batches = Vec::new();
+        let mut remaining = hosts;
+        while remaining > 0 {
+            let batch = size.min(remaining);
+            batches.push(batch);
+            remaining -= batch;
+
+            //the canary is the only host deployed on its own
+            if self.strategy == Strategy::Canary && remaining > 0 {
+                batches.push(remaining);
+                break;
+            }
+        }
+
+        batches
+    }
+}
+
+impl Default for Rollout {
+    fn default() -> Self {
+        Rollout {
+            strategy: Strategy::default(),
+            batch_size: BatchSize::default(),
+            batch_pause: Duration::ZERO,
+            rollback_on_failure: false,
+        }
+    }
+}
+
+struct Deployment<'a> {
+    config: &'a Config,
+    rollout: Rollout,
+    release: String,
+    run_once: Rc<RunOnce>,
+    targets: Vec<Target>,
+    /// Hosts that failed before their deploy started, such as the ones that
+    /// could not be connected to, with their outcome
+    not_started: Vec<(String, &'static str, Duration)>,
+    /// Progress of the failed deploy being resumed
+    resumed: Option<Progress>,
+}
+
+/// Deploys the hosts batch by batch as set by their rollout strategy. Within a
+/// batch hosts are deployed in lockstep: every host prepares the release,
+/// then every host switches to it and only then the post switch tasks run,
+/// so hosts do not serve different releases for longer than the switch
+pub fn deploy(
+    runner: &mut impl Runner,
+    hosts: Vec<(String, Host)>,
+    config: &Config,
+) -> Result<(), Error> {
+    let rollout = Rollout::of(&hosts)?;
+    let batches = rollout.batches(hosts.len());
+    let names = hosts
+        .iter()
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<String>>();
+
+    interrupt::install();
+
+    let mut deployment = Deployment {
+        config,
+        rollout,
+        release: new_release_name(),
+        run_once: Rc::new(RunOnce::default()),
+        targets: Vec::with_capacity(hosts.len()),
+        not_started: Vec::new(),
+        resumed: None,
+    };
+
+    let result = deployment.run(runner, hosts, &batches);
+    deployment.print_summary(&names);
+
+    result
+}
+
+/// Continues the failed deploy of a host in the release it was building, the
+/// tasks it went through are skipped except the ones marked `rerun`
+pub fn resume(
+    runner: &mut impl Runner,
+    name: String,
+    host: Host,
+    config: &Config,
+    progress: Progress,
+) -> Result<(), Error> {
+    let names = vec![name.clone()];
+    let hosts = vec![(name, host)];
+
+    interrupt::install();
+
+    let mut deployment = Deployment {
+        config,
+        rollout: Rollout::of(&hosts)?,
+        release: progress.release.clone(),
+        run_once: Rc::new(RunOnce::default()),
+        targets: Vec::with_capacity(1),
+        not_started: Vec::new(),
+        resumed: Some(progress),
+    };
+
+    let result = deployment.run(runner, hosts, &[1]);
+    deployment.print_summary(&names);
+
+    result
+}
+
+impl Deployment<'_> {
+    fn run(
+        &mut self,
+        runner: &mut impl Runner,
+        hosts: Vec<(String, Host)>,
+        batches: &[usize],
+    ) -> Result<(), Error> {
+        let mut hosts = hosts.into_iter();
+
+        for (index, size) in batches.iter().enumerate() {
+            if index > 0 && !self.rollout.batch_pause.is_zero() {
+                println!(
+                    "Pausing for {}s before the next batch",
+                    self.rollout.batch_pause.as_secs()
+                );
+
+                if !self.config.dry_run {
+                    interrupt::sleep(self.rollout.batch_pause)?;
+                }
+            }
+
+            if batches.len() > 1 {
+                println!("Deploying batch {} of {}", index + 1, batches.len());
+            }
+
+            let start = self.targets.len();
+            for (name, host) in hosts.by_ref().take(*size) {
+                let started_at = Instant::now();
+                let graph = runner
+                    .describe(&host)
+                    .and_then(|graph| self.filter().check(&graph).map(|_| graph));
+
+                let graph = match graph {
+                    Ok(graph) => graph,
+                    Err(err) => {
+                        self.not_started
+                            .push((name.clone(), "failed", started_at.elapsed()));
+                        self.fail(None, Error::Host(name, Box::new(err)))?;
+                        continue;
+                    }
+                };
+
+                match self.connect(name.clone(), host, graph) {
+                    Ok(mut target) => match self.progress(&target) {
+                        Ok(progress) => {
+                            target.ctx.progress = Some(progress);
+                            self.targets.push(target);
+                        }
+                        Err(err) => {
+                            self.not_started
+                                .push((name.clone(), "failed", started_at.elapsed()));
+                            self.fail(None, Error::Host(name, Box::new(err)))?;
+                        }
+                    },
+                    Err(err) => {
+                        self.not_started
+                            .push((name, "unreachable", started_at.elapsed()));
+                        self.fail(None, err)?;
+                    }
+                }
+            }
+
+            self.deploy_batch(start)?;
+        }
+
+        let failed = self
+            .targets
+            .iter()
+            .filter(|target| !target.completed)
+            .map(|target| target.name.clone())
+            .chain(self.not_started.iter().map(|(name, _, _)| name.clone()))
+            .collect::<Vec<String>>();
+
+        if !failed.is_empty() {
+            Error::FailedHosts(failed)?
+        }
+
+        Ok(())
+    }
+
+    fn deploy_batch(&mut self, start: usize) -> Result<(), Error> {
+        for (step, (label, phases)) in STEPS.into_iter().enumerate() {
+            for index in start..self.targets.len() {
+                let target = &mut self.targets[index];
+                if target.aborted {
+                    continue;
+                }
+
+                println!("{} host {}", label, target.name);
+                target.started = true;
+                target.switched |= phases.contains(&Phase::Switch);
+
+                if let Err(err) = target.run_phases(phases.clone()) {
+                    self.fail(Some(index), err)?;
+                } else if step == STEPS.len() - 1 {
+                    target.completed = true;
+                    target.finished_at = Some(Instant::now());
+
+                    if !self.config.dry_run
+                        && let Some(progress) = &target.ctx.progress
+                    {
+                        progress.discard()?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn filter(&self) -> TaskFilter {
+        TaskFilter {
+            skip: self.config.skip.clone(),
+            only: self.config.only.clone(),
+        }
+    }
+
+    fn connect(&self, name: String, host: Host, graph: TaskGraph) -> Result<Target, Error> {
+        let started_at = Instant::now();
+
+        let access = if self.config.dry_run {
+            println!("Planning host {}", name);
+            Access::DryRun(host.path.clone())
+        } else {
+            println!("Deploying host {}", name);
+            access::to(&host.path, &host.ssh)
+                .map_err(|err| Error::Host(name.clone(), Box::new(err)))?
+        };
+
+        let mut ctx = Context::new(host, access, &self.release, self.run_once.clone());
+        ctx.filter = self.filter();
+
+        Ok(Target {
+            name,
+            ctx,
+            graph,
+            started_at,
+            finished_at: None,
+            started: false,
+            switched: false,
+            completed: false,
+            aborted: false,
+            rolled_back: false,
+            failed_task: None,
+        })
+    }
+
+    /// Progress of the resumed host, or a new one replacing the progress left
+    /// by an earlier failed deploy of the host
+    fn progress(&mut self, target: &Target) -> Result<Progress, Error> {
+        let dry_run = self.config.dry_run;
+
+        let Some(progress) = self.resumed.take() else {
+            let progress = Progress::new(self.config, &target.name, &self.release)?;
+            if !dry_run {
+                progress.discard()?;
+            }
+
+            return Ok(progress);
+        };
+
+        if !dry_run {
+            progress::check_revision(&target.ctx, &target.name)?;
+        }
+
+        Ok(progress)
+    }
+
+    /// Stops the deploy of every host, unless `--keep-going` is given in
+    /// which case only the failing host is aborted and the error reported
+    fn fail(&mut self, failed: Option<usize>, err: Error) -> Result<(), Error> {
+        if interrupt::requested() {
+            return Err(self.interrupt(err));
+        }
+
+        if !self.config.keep_going {
+            return Err(abort(&mut self.targets, &self.rollout, err));
+        }
+
+        let err = match failed {
+            Some(index) => abort(&mut self.targets[index..=index], &self.rollout, err),
+            None => err,
+        };
+
+        eprintln!("{}", err);
+        Ok(())
+    }
+
+    /// Aborts every host, their release is removed by the failure tasks unless
+    /// `--keep-release` is given in which case it can still be resumed
+    fn interrupt(&mut self, err: Error) -> Error {
+        //the failure tasks would be stopped too otherwise
+        interrupt::handled();
+
+        let discard = !self.config.keep_release;
+        for target in &mut self.targets {
+            target.ctx.discard_release = discard;
+        }
+
+        let err = abort(&mut self.targets, &self.rollout, err);
+        if !discard || self.config.dry_run {
+            return err;
+        }
+
+        for target in self.targets.iter_mut().filter(|target| target.aborted) {
+            if let Some(progress) = target.ctx.progress.take()
+                && let Err(discard_err) = progress.discard()
+            {
+                eprintln!("{}", discard_err);
+            }
+        }
+
+        err
+    }
+
+    fn print_summary(&self, names: &[String]) {
+        let header = ["HOST", "RESULT", "DURATION", "FAILED TASK"];
+
+        let rows = names
+            .iter()
+            .map(|name| {
+                let target = self.targets.iter().find(|target| &target.name == name);
+                let not_started = self.not_started.iter().find(|(host, _, _)| host == name);
+
+                let (outcome, duration, task) = match (target, not_started) {
+                    (Some(target), _) => (
+                        target.outcome(),
+                        target
+                            .finished_at
+                            .map(|finished_at| finished_at - target.started_at),
+                        target.failed_task.clone(),
+                    ),
+                    (None, Some((_, outcome, duration))) => (*outcome, Some(*duration), None),
+                    (None, None) => ("skipped", None, None),
+                };
+
+                [
+                    name.clone(),
+                    outcome.to_string(),
+                    duration
+                        .map(|duration| format!("{:.1}s", duration.as_secs_f64()))
+                        .unwrap_or_else(|| String::from("-")),
+                    task.unwrap_or_else(|| String::from("-")),
+                ]
+            })
+            .collect::<Vec<[String; 4]>>();
+
+        let mut widths = header.map(str::len);
+        for row in &rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.len());
+            }
+        }
+
+        let print_row = |cells: &[&str]| {
+            let line = cells
+                .iter()
+                .zip(widths)
+                .map(|(cell, width)| format!("{:width$}", cell, width = width))
+                .collect::<Vec<String>>()
+                .join("  ");
+
+            println!("{}", line.trim_end());
+        };
+
+        println!();
+        print_row(&header);
+        for row in &rows {
+            print_row(&row.each_ref().map(String::as_str));
+        }
+
+        let resumable = self
+            .targets
+            .iter()
+            .any(|target| target.started && !target.completed && target.ctx.progress.is_some());
+
+        if resumable && !self.config.dry_run {
+            println!();
+            println!("Continue the deploy of a failed host with `ettac resume <host>`");
+        }
+    }
+}
+
+/// Runs the failure tasks of the hosts that started but did not complete
+/// their deploy. Switched hosts are rolled back first when the rollout says
+/// so, the failing host also when it has `auto_rollback`
+fn abort(targets: &mut [Target], rollout: &Rollout, err: Error) -> Error {
+    let mut rollbacks = Vec::new();
+
+    for target in targets
+        .iter_mut()
+        .filter(|target| target.started && !target.aborted)
+    {
+        let rollback = match target.failed_task.is_some() {
+            true => target.ctx.host.auto_rollback || rollout.rollback_on_failure,
+            false => rollout.rollback_on_failure,
+        };
+
+        if rollback
+            && target.switched
+            && let Err(rollback_err) = target.rollback()
+        {
+            rollbacks.push(rollback_err);
+        }
+
+        if !target.completed {
+            println!("Aborting host {}", target.name);
+            target.graph.fail(&mut target.ctx);
+            target.aborted = true;
+            target.finished_at = Some(Instant::now());
+        }
+    }
+
+    if rollbacks.is_empty() {
+        return err;
+    }
+
+    Error::Rollback {
+        source: Box::new(err),
+        rollbacks,
+    }
+}
@@ -1,4 +1,5 @@
 use crate::access::AccessError;
+use crate::suggestion::did_you_mean;
 use mlua::prelude::LuaError;
 use thiserror::Error as ThisError;
 
@@ -49,14 +50,87 @@ pub enum Error {
     ScriptRuntime(LuaError),
     #[error("ssh error : {0}")]
     Ssh(#[from] libssh_rs::Error),
+    #[error("could not connect to `{hostname}` after {attempts} attempt(s): {source}")]
+    Connection {
+        hostname: String,
+        attempts: u32,
+        source: libssh_rs::Error,
+    },
+    #[error("authentication to `{0}` was denied")]
+    Authentication(String),
     #[error("string `{0}` is not a valid base64 string")]
     InvalidBase64(String),
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
     #[error("unparseable command: {0}")]
     UnparseableCommand(String),
+    #[error("command is empty")]
+    EmptyCommand,
+    #[error("`{0}` is not a valid environment variable name")]
+    InvalidEnvName(String),
     #[error("access error: {0}")]
     Access(#[from] AccessError),
+    #[error("command `{command}` exited with status {status}: {stderr}")]
+    CommandFailed {
+        command: String,
+        status: i32,
+        stderr: String,
+    },
+    #[error("unknown variable `{name}`{}", did_you_mean(.suggestion))]
+    UnknownVariable {
+        name: String,
+        suggestion: Option<String>,
+    },
+    #[error("could not send `{from}` to `{dest}`: {source}")]
+    Send {
+        from: String,
+        dest: String,
+        source: Box<Error>,
+    },
+    #[error("commands can not run during a dry run")]
+    DryRun,
+    #[error("the task timeout was reached, the command was stopped")]
+    Timeout,
+    #[error("the deploy was interrupted")]
+    Interrupted,
+    #[error("task `{0}` failed: {1}")]
+    Task(String, Box<Error>),
+    #[error("unknown task `{name}`{}", did_you_mean(.suggestion))]
+    UnknownTask {
+        name: String,
+        suggestion: Option<String>,
+    },
+    #[error("task name `{name}` is ambiguous, use one of {}", .tasks.join(", "))]
+    AmbiguousTask { name: String, tasks: Vec<String> },
+    #[error("host `{0}` has no current release")]
+    NoCurrentRelease(String),
+    #[error(
+        "another deploy of host `{host}` is running, remove {path}/.ettac.lock if it is not the case"
+    )]
+    DeployRunning { host: String, path: String },
+    #[error("host `{0}`: {1}")]
+    Host(String, Box<Error>),
+    #[error("deploy failed on {} host(s): {}", .0.len(), .0.join(", "))]
+    FailedHosts(Vec<String>),
+    #[error("no failed deploy of host `{0}` to resume")]
+    NothingToResume(String),
+    #[error("invalid progress file `{0:?}`, remove it to start a new deploy")]
+    InvalidProgress(std::path::PathBuf),
+    #[error("the script changed since the deploy of host `{0}` failed, start a new deploy instead")]
+    RecipeChanged(String),
+    #[error(
+        "the repository of host `{host}` moved from `{cloned}` to `{latest}` since the deploy failed, start a new deploy instead"
+    )]
+    RevisionChanged {
+        host: String,
+        cloned: String,
+        latest: String,
+    },
+    #[error("{source}\nrollback failed:\n{}", join_errors(.rollbacks))]
+    Rollback {
+        source: Box<Error>,
+        rollbacks: Vec<Error>,
+    },
 }
 
 impl_error_try!(Error);
@@ -72,10 +146,63 @@ pub enum SetupError {
     MissingPath,
     #[error("missing ssh credentials {0:?}")]
     MissingCredentials(Vec<&'static str>),
+    #[error("unknown become method `{0}`, expected `sudo` or `su`")]
+    InvalidBecomeMethod(String),
+    #[error("unknown rollout strategy `{0}`, expected `all`, `canary` or `rolling`")]
+    InvalidStrategy(String),
+    #[error("invalid batch size `{0}`, expected a number of hosts or a percentage such as `25%`")]
+    InvalidBatchSize(String),
+    #[error("hosts deployed together must share the rollout settings, `{0}` and `{1}` differ")]
+    MixedRollout(String, String),
+    #[error("host `{0}` is defined more than once")]
+    DuplicateHost(String),
+    #[error("invalid inventory `{origin}`: {reason}")]
+    InvalidInventory { origin: String, reason: String },
+    #[error("invalid override `{key}`: {reason}")]
+    InvalidOverride { key: String, reason: String },
+    #[error("stage `{0}` is defined more than once")]
+    DuplicateStage(String),
+    #[error("`{0}` is the name of both a host and a stage")]
+    AmbiguousName(String),
+    #[error("unknown stage `{stage}` for host `{host}`{}", did_you_mean(.suggestion))]
+    UnknownStage {
+        host: String,
+        stage: String,
+        suggestion: Option<String>,
+    },
+    #[error("host `{host}` extends unknown host `{extends}`{}", did_you_mean(.suggestion))]
+    UnknownExtendedHost {
+        host: String,
+        extends: String,
+        suggestion: Option<String>,
+    },
+    #[error("hosts extend each other: {}", .0.join(" -> "))]
+    ExtendsCycle(Vec<String>),
+    #[error("unknown key `{key}` in {location}{}", unknown_key_hint(.suggestion))]
+    UnknownKey {
+        location: String,
+        key: String,
+        suggestion: Option<String>,
+    },
 }
 
 impl_error_try!(SetupError);
 
+fn join_errors(errors: &[Error]) -> String {
+    errors
+        .iter()
+        .map(|err| err.to_string().trim_end().to_string())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+fn unknown_key_hint(suggestion: &Option<String>) -> String {
+    match suggestion {
+        Some(_) => did_you_mean(suggestion),
+        None => String::from(", custom keys go in `vars`"),
+    }
+}
+
 impl From<Error> for LuaError {
     fn from(err: Error) -> Self {
         LuaError::external(err)
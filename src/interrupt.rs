@@ -0,0 +1,117 @@
+use crate::Error;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Set by the first signal, the next one exits right away
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+/// Set until the deploy stopped and started cleaning up the hosts
+static STOP_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// Process groups of the local commands running, killed by the signal that
+/// exits right away as the commands do not get the signals of the terminal
+static CHILDREN: [AtomicI32; MAX_CHILDREN] = [const { AtomicI32::new(0) }; MAX_CHILDREN];
+
+const MAX_CHILDREN: usize = 64;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Exit status of a process killed by SIGINT, as reported by shells
+const FORCED_EXIT_STATUS: i32 = 130;
+
+/// Handles SIGINT and SIGTERM by asking the deploy to stop, it then stops the
+/// commands running and the hosts are cleaned up with their failure tasks
+pub fn install() {
+    let handler = handle as extern "C" fn(libc::c_int);
+
+    unsafe {
+        let mut action = std::mem::zeroed::<libc::sigaction>();
+        action.sa_sigaction = handler as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+
+        libc::sigaction(libc::SIGINT, &action, std::ptr::null_mut());
+        libc::sigaction(libc::SIGTERM, &action, std::ptr::null_mut());
+    }
+}
+
+extern "C" fn handle(_: libc::c_int) {
+    //only async signal safe functions can be called from here
+    if INTERRUPTED.swap(true, Ordering::SeqCst) {
+        for child in &CHILDREN {
+            let group = child.load(Ordering::SeqCst);
+            if group != 0 {
+                unsafe { libc::kill(-group, libc::SIGKILL) };
+            }
+        }
+
+        unsafe { libc::_exit(FORCED_EXIT_STATUS) };
+    }
+
+    STOP_REQUESTED.store(true, Ordering::SeqCst);
+
+    let message = b"\nInterrupted, stopping the deploy, interrupt again to exit right away\n";
+    unsafe { libc::write(libc::STDERR_FILENO, message.as_ptr().cast(), message.len()) };
+}
+
+/// Process group of a local command, killed if the deploy exits before the
+/// command ends
+pub struct Child {
+    slot: Option<&'static AtomicI32>,
+}
+
+impl Child {
+    /// Registers a process group, it is only killed by the first signal when
+    /// more than [`MAX_CHILDREN`] commands run at once
+    pub fn register(group: u32) -> Self {
+        let slot = CHILDREN.iter().find(|slot| {
+            slot.compare_exchange(0, group as i32, Ordering::SeqCst, Ordering::SeqCst)
+                .is_ok()
+        });
+
+        Child { slot }
+    }
+}
+
+impl Drop for Child {
+    fn drop(&mut self) {
+        if let Some(slot) = self.slot {
+            slot.store(0, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Whether the deploy has to stop, the commands running are stopped and no
+/// task is started until it is [`handled`]
+pub fn requested() -> bool {
+    STOP_REQUESTED.load(Ordering::SeqCst)
+}
+
+pub fn check() -> Result<(), Error> {
+    if requested() {
+        Err(Error::Interrupted)?
+    }
+
+    Ok(())
+}
+
+/// Lets the commands cleaning up the hosts run, a new signal then exits
+pub fn handled() {
+    STOP_REQUESTED.store(false, Ordering::SeqCst);
+}
+
+/// Sleeps unless the deploy is interrupted in the meantime
+pub fn sleep(duration: Duration) -> Result<(), Error> {
+    let end = Instant::now() + duration;
+
+    loop {
+        check()?;
+
+        let left = end.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Ok(());
+        }
+
+        thread::sleep(left.min(POLL_INTERVAL));
+    }
+}
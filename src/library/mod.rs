@@ -1,7 +1,12 @@
 use crate::Error;
-use crate::context::Context;
+use crate::access::{self, Access, AccessError, CommandLine, CommandOptions, Escalation};
+use crate::context::{Become, Context};
+use crate::suggestion;
 use base64::prelude::*;
+use std::collections::BTreeMap;
 use std::env;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
 pub fn env(name: &str, default: Option<String>) -> Option<String> {
     env::var(name).ok().or(default)
@@ -11,6 +16,14 @@ pub fn base64_encode(value: &str) -> String {
     BASE64_STANDARD.encode(value.as_bytes())
 }
 
+pub fn shell_quote(value: &str) -> Result<String, Error> {
+    access::quote(value)
+}
+
+pub fn shell_join(args: Vec<String>) -> Result<String, Error> {
+    CommandLine::Argv(args).to_shell()
+}
+
 pub fn base64_decode(value: &str) -> Result<String, Error> {
     let decoded = BASE64_STANDARD
         .decode(value)
@@ -21,22 +34,267 @@ pub fn base64_decode(value: &str) -> Result<String, Error> {
     Ok(string)
 }
 
-pub fn set_timeout(_: &Context, timeout: i32) {
-    println!("Setting timeout to {}", timeout);
+/// Gives the current task `timeout` seconds from now to complete, there is
+/// no limit when it is not positive
+pub fn set_timeout(ctx: &Context, timeout: i32) {
+    let deadline = u64::try_from(timeout)
+        .ok()
+        .filter(|timeout| *timeout > 0)
+        .map(|timeout| Instant::now() + Duration::from_secs(timeout));
+
+    ctx.deadline.set(deadline);
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RunOptions {
+    pub cwd: Option<String>,
+    pub env: BTreeMap<String, String>,
+    pub become_user: Become,
+}
+
+/// Runs a command from the directory ettac was started in
+pub fn local(ctx: &Context, command: &CommandLine, options: RunOptions) -> Result<String, Error> {
+    let command = render_command(ctx, command)?;
+    if ctx.plan.is_none() {
+        println!("Running command locally: {}", command);
+    }
+
+    let access = Access::Local(String::from("."));
+    run(ctx, &access, &command, options)
+}
+
+/// Runs a command on the host, from the release directory by default
+pub fn remote(
+    ctx: &Context,
+    command: &CommandLine,
+    mut options: RunOptions,
+) -> Result<String, Error> {
+    let command = render_command(ctx, command)?;
+    if ctx.plan.is_none() {
+        println!("Running command on remote host: {}", command);
+    }
+
+    options
+        .cwd
+        .get_or_insert_with(|| format!("releases/{}", ctx.release));
+
+    run(ctx, &ctx.access, &command, options)
+}
+
+/// Replaces the `{{name}}` placeholders with the built-in variables of the
+/// deploy or the `vars` of the host. Values are inserted as they are, they
+/// should be given to `shell_quote` first if they are not trusted.
+///
+/// Only names such as `release` or `php.bin` are placeholders, the templates
+/// of other tools such as `{{.State}}` are left as they are. `{{{{` is
+/// replaced by a literal `{{` for the ones that look like a placeholder.
+pub fn render(ctx: &Context, template: &str) -> Result<String, Error> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        if rest[start..].starts_with("{{{{") {
+            output.push_str(&rest[..start + 2]);
+            rest = &rest[start + 4..];
+            continue;
+        }
+
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+
+        let name = rest[start + 2..start + end].trim();
+        if !is_variable_name(name) {
+            output.push_str(&rest[..start + 2]);
+            rest = &rest[start + 2..];
+            continue;
+        }
+
+        let value = match name {
+            "path" => ctx.host.path.clone(),
+            "release" => ctx.release.clone(),
+            "release_path" => ctx.release_path(),
+            "shared_path" => ctx.shared_path(),
+            "current_path" => ctx.current_path(),
+            name => match ctx.host.vars.get(name) {
+                Some(value) => value.clone(),
+                None => {
+                    let names = BUILTIN_VARIABLES
+                        .into_iter()
+                        .chain(ctx.host.vars.keys().map(String::as_str));
+
+                    Err(Error::UnknownVariable {
+                        name: name.to_string(),
+                        suggestion: suggestion::closest(name, names).map(str::to_string),
+                    })?
+                }
+            },
+        };
+
+        output.push_str(&rest[..start]);
+        output.push_str(&value);
+        rest = &rest[start + end + 2..];
+    }
+
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Dotted identifiers, the shape of the keys of `vars`
+fn is_variable_name(name: &str) -> bool {
+    name.split('.').all(|part| {
+        let mut chars = part.chars();
+
+        chars
+            .next()
+            .is_some_and(|char| char.is_ascii_alphabetic() || char == '_')
+            && chars.all(|char| char.is_ascii_alphanumeric() || char == '_' || char == '-')
+    })
 }
 
-pub fn local(_: &Context, command: &str) {
-    println!("Running command locally: {}", command);
+const BUILTIN_VARIABLES: [&str; 5] = [
+    "path",
+    "release",
+    "release_path",
+    "shared_path",
+    "current_path",
+];
+
+fn render_command(ctx: &Context, command: &CommandLine) -> Result<CommandLine, Error> {
+    Ok(match command {
+        CommandLine::Shell(cmd) => CommandLine::Shell(render(ctx, cmd)?),
+        CommandLine::Argv(args) => CommandLine::Argv(
+            args.iter()
+                .map(|arg| render(ctx, arg))
+                .collect::<Result<Vec<String>, Error>>()?,
+        ),
+    })
 }
 
-pub fn remote(_: &Context, command: &str) {
-    println!("Running command on remote host: {}", command);
+fn run(
+    ctx: &Context,
+    access: &Access,
+    command: &CommandLine,
+    mut options: RunOptions,
+) -> Result<String, Error> {
+    options.cwd = options.cwd.map(|cwd| render(ctx, &cwd)).transpose()?;
+    let become_user = options.become_user.or(ctx.task.become_user.clone());
+
+    //variables of the command override the ones of the host
+    let mut env = ctx.host.env.clone();
+    env.extend(options.env);
+
+    if let Some(plan) = &ctx.plan {
+        let command_options = CommandOptions {
+            cwd: options.cwd,
+            env,
+            escalation: escalation(ctx, become_user),
+            deadline: None,
+        };
+
+        plan.command(access, command, &command_options);
+        return Ok(String::new());
+    }
+
+    loop {
+        let command_options = CommandOptions {
+            cwd: options.cwd.clone(),
+            env: env.clone(),
+            escalation: escalation(ctx, become_user.clone()),
+            deadline: ctx.deadline.get(),
+        };
+
+        let result = match access.run_with(command, &command_options) {
+            Err(Error::Access(AccessError::BecomePasswordRequired)) => {
+                //without a terminal to ask on, the password has to be configured
+                let Ok(password) = rpassword::prompt_password("Password to become another user: ")
+                else {
+                    return Err(AccessError::BecomePasswordRequired.into());
+                };
+                ctx.become_password.replace(Some(password));
+                continue;
+            }
+            result => result?,
+        };
+
+        if result.status != 0 {
+            Err(Error::CommandFailed {
+                command: command.to_string(),
+                status: result.status,
+                stderr: result.stderr.trim_end().to_string(),
+            })?
+        }
+
+        return Ok(result.stdout.trim_end().to_string());
+    }
 }
 
-pub fn send(_: &Context, from: &str, dest: Option<&str>) {
-    println!(
-        "Sending file {} to remote host {}",
-        from,
-        dest.unwrap_or(from)
-    );
+fn escalation(ctx: &Context, become_user: Become) -> Option<Escalation> {
+    let host_user = match &ctx.host.become_user {
+        Some(user) => Become::User(user.clone()),
+        None => Become::Disabled,
+    };
+
+    match become_user.or(host_user) {
+        Become::User(user) => Some(Escalation {
+            user,
+            method: ctx.host.become_method,
+            password: ctx.become_password.borrow().clone(),
+        }),
+        Become::Inherit | Become::Disabled => None,
+    }
+}
+
+/// Points `link` to `target`, relative paths are resolved from the deploy path
+pub fn symlink(ctx: &Context, target: &str, link: &str) -> Result<(), Error> {
+    if let Some(plan) = &ctx.plan {
+        plan.symlink(target, link, &ctx.host.path);
+        return Ok(());
+    }
+
+    let command = CommandLine::Argv(vec![
+        String::from("ln"),
+        String::from("-sfn"),
+        target.to_string(),
+        link.to_string(),
+    ]);
+
+    let options = RunOptions {
+        cwd: Some(ctx.host.path.clone()),
+        ..RunOptions::default()
+    };
+
+    remote(ctx, &command, options)?;
+    Ok(())
+}
+
+/// Uploads a local file or directory to the host, relative paths are resolved
+/// like the ones of the commands: from the local working directory for the
+/// source and from the release directory for the destination
+pub fn send(ctx: &Context, from: &str, dest: Option<&str>) -> Result<(), Error> {
+    let from = render(ctx, from)?;
+    let dest = dest.map(|dest| render(ctx, dest)).transpose()?;
+    let dest = dest.as_deref().unwrap_or(&from);
+
+    if let Some(plan) = &ctx.plan {
+        plan.upload(&from, dest);
+        return Ok(());
+    }
+
+    println!("Sending file {} to remote host {}", from, dest);
+
+    let resolved = match dest.starts_with('/') {
+        true => dest.to_string(),
+        false => ctx
+            .access
+            .resolve_dir(Some(&format!("releases/{}/{}", ctx.release, dest))),
+    };
+
+    ctx.access
+        .upload(Path::new(&from), &resolved)
+        .map_err(|err| Error::Send {
+            from: from.clone(),
+            dest: dest.to_string(),
+            source: Box::new(err),
+        })
 }
@@ -0,0 +1,129 @@
+-- Release lifecycle shared by every recipe: releases are cloned in
+-- `releases/<release>` and `current` is switched to the new release once
+-- every build task succeeded
+
+System = {}
+System.__index = System
+
+local function at_root()
+    return { cwd = deploy.path }
+end
+
+local function dirname(path)
+    return string.match(path, "^(.*)/[^/]*$") or "."
+end
+
+local function link(shared, target)
+    remote("mkdir -p " .. shell_quote(dirname(target)))
+    remote("rm -rf " .. shell_quote(target))
+    symlink(shared, target)
+end
+
+function System:new()
+    return setmetatable({}, System)
+end
+
+function System:describe()
+    task(self.lock, { phase = "setup", rerun = true })
+    task(self.prepare, { phase = "setup" })
+    task(self.checkout, { phase = "setup" })
+    task(self.link_persistent, { phase = "setup" })
+    task(self.switch, { phase = "switch" })
+    task(self.cleanup, { phase = "finalize" })
+    task(self.unlock, { phase = "finalize" })
+    task(self.fail, { phase = "failure" })
+    task(self.rollback, { phase = "rollback" })
+end
+
+function System:lock()
+    remote("mkdir -p " .. shell_quote(deploy.path), { cwd = "/" })
+
+    -- mkdir fails when the directory exists, so only one deploy can take it
+    local held = "another deploy is running, remove " .. deploy.path .. "/.ettac.lock if it is not the case"
+    remote("mkdir .ettac.lock 2>/dev/null || { echo " .. shell_quote(held) .. " >&2; exit 1; }", at_root())
+    self.locked = true
+end
+
+function System:prepare()
+    remote("mkdir -p releases shared", at_root())
+end
+
+function System:checkout()
+    remote("git clone --depth 1 " .. shell_quote(deploy.repository) .. " " .. shell_quote("releases/" .. deploy.release), at_root())
+end
+
+function System:link_persistent()
+    for _, dir in ipairs(deploy.persistent_dirs) do
+        local shared = deploy.shared_path .. "/" .. dir
+
+        remote("mkdir -p " .. shell_quote(shared))
+        link(shared, deploy.release_path .. "/" .. dir)
+    end
+
+    for _, file in ipairs(deploy.persistent_files) do
+        local shared = deploy.shared_path .. "/" .. file
+
+        remote("mkdir -p " .. shell_quote(dirname(shared)))
+        remote("touch " .. shell_quote(shared))
+        link(shared, deploy.release_path .. "/" .. file)
+    end
+end
+
+function System:switch()
+    self.previous = remote("readlink current || true", at_root()):match("[^\n]+")
+    symlink("releases/" .. deploy.release, "current")
+    self.switched = true
+end
+
+function System:cleanup()
+    if deploy.keep_releases <= 0 then
+        return
+    end
+
+    -- the release that was live before this deploy is kept until the deploy
+    -- completed as it is the one a rollback switches back to
+    local previous = self.previous and self.previous:match("[^/]+$")
+
+    local releases = {}
+    for release in string.gmatch(remote("ls -1 releases", at_root()), "[^\n]+") do
+        if release ~= previous then
+            table.insert(releases, release)
+        end
+    end
+
+    -- release names sort chronologically
+    table.sort(releases, function(a, b) return a > b end)
+
+    for i = deploy.keep_releases + 1, #releases do
+        remote("rm -rf " .. shell_quote("releases/" .. releases[i]), at_root())
+    end
+end
+
+function System:unlock()
+    remote("rm -rf .ettac.lock", at_root())
+    self.locked = false
+end
+
+-- puts back the release that was live before this deploy, if there was one
+function System:rollback()
+    if self.switched and self.previous then
+        local missing = "previous release " .. self.previous .. " no longer exists, current was left unchanged"
+        remote("test -d " .. shell_quote(self.previous) .. " || { echo " .. shell_quote(missing) .. " >&2; exit 1; }", at_root())
+        symlink(self.previous, "current")
+        self.switched = false
+    end
+end
+
+-- the release of a failed deploy is kept so `ettac resume` can continue the
+-- deploy in it, the ones never resumed go away with `keep_releases`. An
+-- interrupted deploy removes it unless it went live
+function System:fail()
+    local release = "releases/" .. deploy.release
+    if deploy.discard_release and remote("readlink current || true", at_root()):match("[^\n]+") ~= release then
+        remote("rm -rf " .. shell_quote(release), at_root())
+    end
+
+    if self.locked then
+        self:unlock()
+    end
+end
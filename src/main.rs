@@ -3,16 +3,24 @@
 #![allow(dead_code)]
 
 mod access;
+mod commands;
 mod config;
 mod context;
+mod deployment;
 mod error;
+mod interrupt;
 mod library;
+mod plan;
+mod progress;
 mod runners;
+mod suggestion;
+mod tasks;
 
 use error::Error;
+use std::collections::HashMap;
 
-use crate::config::Config;
-use crate::context::{Context, Host};
+use crate::config::{Command, Config};
+use crate::context::Host;
 use crate::runners::{LuaRunner, Runner};
 
 fn main() {
@@ -29,31 +37,64 @@ fn with_lua(config: &'static Config) -> Result<(), Error> {
     let mut runner = LuaRunner::new(config);
     runner.init()?;
 
-    let hosts = runner.get_hosts()?;
-    let unknown_hosts = config
-        .hosts
-        .iter()
-        .filter(|host| !hosts.contains_key(*host))
-        .cloned()
-        .collect::<Vec<String>>();
-
-    if !unknown_hosts.is_empty() {
-        Error::UnknownHosts(unknown_hosts)?
+    match &config.command {
+        Some(Command::Hosts(command)) => return commands::hosts(&mut runner, command),
+        Some(Command::Tasks(command)) => return commands::tasks(&mut runner, command),
+        Some(Command::RunTask(command)) => {
+            return commands::run_task(&mut runner, command, config.dry_run);
+        }
+        Some(Command::Resume(command)) => return commands::resume(&mut runner, command, config),
+        None => {}
     }
 
-    let hosts = hosts
-        .into_iter()
-        .filter(|(name, _)| config.hosts.contains(name))
-        .collect::<Vec<(String, Host)>>();
+    let hosts = select_hosts(&config.hosts, runner.get_hosts()?)?;
+
+    deployment::deploy(&mut runner, hosts, config)
+}
+
+/// Targets given on the command line are host or stage names, a stage
+/// selects every host assigned to it
+fn select_hosts(
+    targets: &[String],
+    mut hosts: HashMap<String, Host>,
+) -> Result<Vec<(String, Host)>, Error> {
+    let mut selected = Vec::new();
+    let mut unknown_hosts = Vec::new();
 
-    for (name, host) in hosts {
-        println!("Deploying host {}", name);
+    for target in targets {
+        let names = if hosts.contains_key(target) {
+            vec![target.clone()]
+        } else {
+            let mut names = hosts
+                .iter()
+                .filter(|(_, host)| host.stage.as_ref() == Some(target))
+                .map(|(name, _)| name.clone())
+                .collect::<Vec<String>>();
 
-        let access = access::to(&host.path, &host.ssh)?;
-        let context = Context { host, access };
+            names.sort();
+            names
+        };
 
-        runner.run(context)?;
+        if names.is_empty() {
+            unknown_hosts.push(target.clone());
+        }
+
+        for name in names {
+            if !selected.contains(&name) {
+                selected.push(name);
+            }
+        }
+    }
+
+    if !unknown_hosts.is_empty() {
+        Error::UnknownHosts(unknown_hosts)?
     }
 
-    Ok(())
+    Ok(selected
+        .into_iter()
+        .map(|name| {
+            let host = hosts.remove(&name).expect("selected hosts exist");
+            (name, host)
+        })
+        .collect())
 }
@@ -0,0 +1,64 @@
+use crate::access::{Access, CommandLine, CommandOptions};
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeSet;
+
+/// Steps recorded instead of being run during a dry run. They are printed as
+/// soon as they are recorded so the plan follows the order of the tasks.
+#[derive(Debug, Default)]
+pub struct Plan {
+    steps: Cell<usize>,
+    read_steps: RefCell<BTreeSet<usize>>,
+}
+
+impl Plan {
+    pub fn command(&self, access: &Access, cmd: &CommandLine, options: &CommandOptions) -> usize {
+        let target = match access {
+            Access::Local(_) => "local",
+            Access::Remote(..) | Access::DryRun(_) => "remote",
+        };
+
+        let user = match &options.escalation {
+            Some(escalation) => format!(" as {}", escalation.user),
+            None => String::new(),
+        };
+
+        self.record(format!(
+            "{} in {}{}: {}",
+            target,
+            access.resolve_dir(options.cwd.as_deref()),
+            user,
+            cmd
+        ))
+    }
+
+    pub fn upload(&self, from: &str, dest: &str) -> usize {
+        self.record(format!("upload {} to {}", from, dest))
+    }
+
+    pub fn symlink(&self, target: &str, link: &str, dir: &str) -> usize {
+        self.record(format!("symlink in {}: {} -> {}", dir, link, target))
+    }
+
+    pub fn last_step(&self) -> usize {
+        self.steps.get()
+    }
+
+    /// Marks a step whose output is read by the script, as outputs are empty
+    /// during a dry run the following steps may differ from the real deploy
+    pub fn output_read(&self, step: usize) {
+        if self.read_steps.borrow_mut().insert(step) {
+            println!(
+                "  ! output of #{} is read by the script, the next steps assume it is empty",
+                step
+            );
+        }
+    }
+
+    fn record(&self, description: String) -> usize {
+        let step = self.steps.get() + 1;
+        self.steps.set(step);
+
+        println!("  #{} {}", step, description);
+        step
+    }
+}
@@ -0,0 +1,187 @@
+use crate::Error;
+use crate::access;
+use crate::config::Config;
+use crate::context::Context;
+use crate::tasks::Task;
+use serde_json::{Value, json};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where a host got to in a deploy, saved next to the script after every task
+/// so a failed deploy can be continued with `ettac resume <host>`
+#[derive(Debug)]
+pub struct Progress {
+    pub release: String,
+    /// Fingerprint of the script the deploy was started with
+    pub recipe: String,
+    /// Keys of the tasks that ran on the host, and of the `once` tasks that
+    /// ran on another host, the resumed deploy skips them
+    pub completed: Vec<String>,
+    path: PathBuf,
+}
+
+impl Progress {
+    pub fn new(config: &Config, host: &str, release: &str) -> Result<Self, Error> {
+        Ok(Progress {
+            release: release.to_string(),
+            recipe: fingerprint(config)?,
+            completed: Vec::new(),
+            path: progress_path(config, host),
+        })
+    }
+
+    /// Progress left by the failed deploy of the host, refused when the
+    /// script changed since as the tasks that completed may not match anymore
+    pub fn load(config: &Config, host: &str) -> Result<Self, Error> {
+        let path = progress_path(config, host);
+        if !path.exists() {
+            return Err(Error::NothingToResume(host.to_string()));
+        }
+
+        let invalid = || Error::InvalidProgress(path.clone());
+        let value =
+            serde_json::from_str::<Value>(&fs::read_to_string(&path)?).map_err(|_| invalid())?;
+
+        let string = |key: &str| value[key].as_str().map(str::to_string).ok_or_else(invalid);
+        let progress = Progress {
+            release: string("release")?,
+            recipe: string("recipe")?,
+            completed: value["completed"]
+                .as_array()
+                .and_then(|tasks| {
+                    tasks
+                        .iter()
+                        .map(|task| task.as_str().map(str::to_string))
+                        .collect::<Option<Vec<String>>>()
+                })
+                .ok_or_else(invalid)?,
+            path: path.clone(),
+        };
+
+        if progress.recipe != fingerprint(config)? {
+            return Err(Error::RecipeChanged(host.to_string()));
+        }
+
+        Ok(progress)
+    }
+
+    pub fn has_completed(&self, task: &Task) -> bool {
+        self.completed.contains(&task.key())
+    }
+
+    pub fn complete(&mut self, task: &Task) {
+        if !self.has_completed(task) {
+            self.completed.push(task.key());
+        }
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let value = json!({
+            "release": self.release,
+            "recipe": self.recipe,
+            "completed": self.completed,
+        });
+
+        fs::write(
+            &self.path,
+            serde_json::to_string_pretty(&value).unwrap_or_default(),
+        )?;
+        Ok(())
+    }
+
+    /// Forgets the progress once the deploy completed or was started over
+    pub fn discard(&self) -> Result<(), Error> {
+        if self.path.exists() {
+            fs::remove_file(&self.path)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Refuses to resume a release cloned from a commit its branch moved away
+/// from, the remaining tasks would build a release mixing both revisions
+pub fn check_revision(ctx: &Context, host: &str) -> Result<(), Error> {
+    let release = access::quote(&format!("releases/{}", ctx.release))?;
+    let cloned = ctx
+        .access
+        .run(&format!("git -C {} rev-parse HEAD", release))?;
+
+    //the release was not cloned yet, it will be by the resumed deploy
+    if cloned.status != 0 {
+        return Ok(());
+    }
+
+    //the branch is the one the checkout picked, which is not necessarily the
+    //default branch of the repository
+    let branch = ctx.access.run(&format!(
+        "git -C {} symbolic-ref --quiet --short HEAD",
+        release
+    ))?;
+
+    //a detached release was cloned from a tag or a commit, which do not move
+    if branch.status != 0 {
+        return Ok(());
+    }
+
+    let command = format!(
+        "git ls-remote {} {}",
+        access::quote(&ctx.host.repository)?,
+        access::quote(&format!("refs/heads/{}", branch.stdout.trim()))?
+    );
+    let latest = ctx.access.run(&command)?;
+    if latest.status != 0 {
+        Err(Error::CommandFailed {
+            command,
+            status: latest.status,
+            stderr: latest.stderr,
+        })?
+    }
+
+    let cloned = cloned.stdout.trim();
+    let latest = latest.stdout.split_whitespace().next().unwrap_or_default();
+
+    if cloned != latest {
+        Err(Error::RevisionChanged {
+            host: host.to_string(),
+            cloned: cloned.to_string(),
+            latest: latest.to_string(),
+        })?
+    }
+
+    Ok(())
+}
+
+fn progress_path(config: &Config, host: &str) -> PathBuf {
+    Path::new(&config.script)
+        .with_file_name(".ettac")
+        .join("progress")
+        .join(format!("{}.json", file_name(host)))
+}
+
+/// Percent encodes the host name so names like `a/b` or `../x` stay a file
+/// of the progress directory
+fn file_name(host: &str) -> String {
+    host.bytes()
+        .map(|byte| match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'.' | b'_' | b'-' => {
+                char::from(byte).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+/// FNV-1a hash of the script, stable across builds unlike the std hasher
+fn fingerprint(config: &Config) -> Result<String, Error> {
+    let script = fs::read(&config.script)?;
+    let hash = script.iter().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x100000001b3)
+    });
+
+    Ok(format!("{:016x}", hash))
+}
@@ -1,15 +1,61 @@
+mod inventory;
+mod lists;
+mod overrides;
+mod tasks;
+
 use crate::config::Config;
-use crate::context::{AuthMethod, Callable, Context, Host, PartialHost, PartialSshCredentials};
-use crate::error::Error;
+use crate::context::{
+    AuthMethod, BatchSize, Become, BecomeMethod, Callable, Context, Host, PartialHost,
+    PartialSshCredentials, Strategy,
+};
+use crate::error::{Error, SetupError};
 use crate::library;
 use crate::runners::Runner;
+use crate::suggestion;
+use crate::tasks::TaskGraph;
+use lists::ListOps;
 use mlua::prelude::{LuaError, LuaTable};
 use mlua::{FromLua, Function, IntoLua, Lua, Value};
 use partially::Partial;
-use std::collections::HashMap;
+use std::any::Any;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::rc::Rc;
+use std::time::Duration;
+
+const MODULES: [(&str, &str); 1] = [("system.lua", include_str!("../library/modules/system.lua"))];
+const HOST_KEYS: [&str; 29] = [
+    "extends",
+    "stage",
+    "recipe",
+    "repository",
+    "keep_releases",
+    "persistent_files",
+    "persistent_dirs",
+    "labels",
+    "env",
+    "vars",
+    "path",
+    "become",
+    "become_method",
+    "become_password",
+    "ssh_alias",
+    "hostname",
+    "port",
+    "user",
+    "password",
+    "private_key",
+    "passphrase",
+    "connect_timeout",
+    "keepalive_interval",
+    "connect_retries",
+    "strategy",
+    "batch_size",
+    "batch_pause",
+    "rollback_on_failure",
+    "auto_rollback",
+];
 
 pub struct LuaRunner {
     lua: Lua,
@@ -30,7 +76,42 @@ impl Runner for LuaRunner {
         let lua = &mut self.lua;
         let globals = lua.globals();
 
+        let existing = globals
+            .pairs::<String, Value>()
+            .map(|pair| pair.map(|(name, _)| name))
+            .collect::<Result<Vec<String>, LuaError>>()?;
+
+        for (name, module) in MODULES {
+            lua.load(module).set_name(name).exec()?;
+        }
+
+        //classes defined by the modules are remembered so their tasks can be
+        //told apart from the ones of the script
+        let builtins = lua.create_table()?;
+        for pair in globals.pairs::<String, Value>() {
+            if let (name, Value::Table(class)) = pair?
+                && !existing.contains(&name)
+            {
+                builtins.set(class, true)?;
+            }
+        }
+
+        lua.set_named_registry_value(tasks::BUILTIN_MODULES, builtins)?;
+
         let script = get_script(self.config)?;
+
+        //a dry run tells from the line calling a command whether its output
+        //is used, see `tasks::output_used`
+        if self.config.dry_run {
+            let sources = lua.create_table()?;
+            for (name, module) in MODULES {
+                sources.set(name, module)?;
+            }
+
+            sources.set(self.config.script.as_str(), script.as_str())?;
+            lua.set_named_registry_value(tasks::SOURCES, sources)?;
+        }
+
         lua.load(script).set_name(&self.config.script).exec()?;
 
         let env_fn =
@@ -55,6 +136,22 @@ impl Runner for LuaRunner {
 
         globals.set("base64_decode", base64_decode)?;
 
+        let shell_quote = self.lua.create_function(|lua, (value,): (String,)| {
+            library::shell_quote(&value)
+                .map_err(LuaError::from)?
+                .into_lua(lua)
+        })?;
+
+        globals.set("shell_quote", shell_quote)?;
+
+        let shell_join = self.lua.create_function(|lua, (args,): (Vec<String>,)| {
+            library::shell_join(args)
+                .map_err(LuaError::from)?
+                .into_lua(lua)
+        })?;
+
+        globals.set("shell_join", shell_join)?;
+
         Ok(())
     }
 
@@ -63,8 +160,9 @@ impl Runner for LuaRunner {
         let globals = lua.globals();
         globals.set("defaults", lua.create_table()?)?;
         globals.set("hosts", lua.create_table()?)?;
+        globals.set("stages", lua.create_table()?)?;
 
-        add_setup_functions(lua)?;
+        add_setup_functions(lua, self.config)?;
         lua.load("setup()").exec()?;
         remove_setup_functions(lua)?;
 
@@ -75,19 +173,61 @@ impl Runner for LuaRunner {
             lua.create_table()?
         };
 
+        let layer_keys = HOST_KEYS
+            .into_iter()
+            .filter(|key| !matches!(*key, "stage" | "extends"))
+            .collect::<Vec<&str>>();
+
+        check_keys(&defaults, "default()", &layer_keys)?;
         let defaults = PartialHost::try_from(defaults)?;
 
+        let mut stages = HashMap::new();
+        for pair in globals
+            .get::<LuaTable>("stages")?
+            .pairs::<String, LuaTable>()
+        {
+            let (name, value) = pair?;
+
+            check_keys(&value, &format!("stage `{}`", name), &layer_keys)?;
+            let ops = lists::layer_ops(&value)?;
+            stages.insert(
+                name,
+                merge_host(&defaults, PartialHost::try_from(value)?, &ops),
+            );
+        }
+
         let hosts = globals.get::<LuaTable>("hosts")?;
 
-        let mut parsed_hosts = HashMap::new();
         for pair in hosts.pairs::<String, LuaTable>() {
             let (name, value) = pair?;
 
+            if stages.contains_key(&name) {
+                SetupError::AmbiguousName(name.clone())?
+            }
+
+            check_keys(&value, &format!("host `{}`", name), &HOST_KEYS)?;
+        }
+
+        let overrides = overrides::to_table(lua, &self.config.overrides)?;
+        let overrides = PartialHost::try_from(overrides)?;
+
+        let mut parsed_hosts = HashMap::new();
+        for (name, value) in resolve_extends(lua, &hosts)? {
+            let ops = lists::layer_ops(&value)?;
             let partial_host = PartialHost::try_from(value)?;
-            let mut host_with_defaults = defaults.clone();
-            host_with_defaults.apply_some(partial_host);
 
-            let host = Host::try_from(host_with_defaults)?;
+            let base = match &partial_host.stage {
+                Some(stage) => stages.get(stage).ok_or_else(|| SetupError::UnknownStage {
+                    host: name.clone(),
+                    stage: stage.clone(),
+                    suggestion: suggestion::closest(stage, stages.keys().map(String::as_str))
+                        .map(str::to_string),
+                })?,
+                None => &defaults,
+            };
+
+            let host = merge_host(base, partial_host, &ops);
+            let host = Host::try_from(merge_host(&host, overrides.clone(), &[]))?;
 
             parsed_hosts.insert(name, host);
         }
@@ -95,10 +235,22 @@ impl Runner for LuaRunner {
         Ok(parsed_hosts)
     }
 
-    fn run(&mut self, _: Context) -> Result<(), Error> {
-        //TODO: populate functions
+    fn describe(&mut self, host: &Host) -> Result<TaskGraph, Error> {
+        let recipe = host
+            .recipe
+            .as_any()
+            .downcast_ref::<LuaRecipe>()
+            .expect("hosts parsed by the lua runner have lua recipes");
+
+        let recipe = match recipe.instantiate()? {
+            Value::Table(recipe) => recipe,
+            value => Err(LuaError::runtime(format!(
+                "recipe must return an object with a describe method, got {}",
+                value.type_name()
+            )))?,
+        };
 
-        Ok(())
+        tasks::describe(&self.lua, recipe)
     }
 }
 
@@ -111,7 +263,14 @@ fn get_script(args: &Config) -> Result<String, Error> {
     Ok(fs::read_to_string(script_path)?)
 }
 
-fn add_setup_functions(lua: &Lua) -> Result<(), LuaError> {
+fn add_setup_functions(lua: &Lua, config: &Config) -> Result<(), LuaError> {
+    lists::add_setup_functions(lua)?;
+    let dir = match Path::new(&config.script).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    inventory::add_setup_functions(lua, dir, !config.no_inventory_commands)?;
+
     let globals = lua.globals();
     globals.set("defaults", lua.create_table()?)?;
     globals.set("hosts", lua.create_table()?)?;
@@ -124,7 +283,7 @@ fn add_setup_functions(lua: &Lua) -> Result<(), LuaError> {
             lua.create_table()?
         };
 
-        globals.set("defaults", merge_tables(&current_defaults, &data)?)?;
+        globals.set("defaults", merge_tables(lua, &current_defaults, &data)?)?;
 
         Ok(())
     })?;
@@ -132,13 +291,34 @@ fn add_setup_functions(lua: &Lua) -> Result<(), LuaError> {
     globals.set("default", default_fn)?;
 
     let host_fn = lua.create_function_mut(|lua, (name, data): (String, LuaTable)| {
-        let hosts = lua.globals().get::<LuaTable>("hosts")?;
-        hosts.set(name, data)?;
+        Ok(add_host(lua, name, data)?)
+    })?;
+
+    globals.set("host", host_fn)?;
+
+    let stage_fn = lua.create_function_mut(|lua, (name, data): (String, LuaTable)| {
+        let stages = lua.globals().get::<LuaTable>("stages")?;
+        if stages.contains_key(name.as_str())? {
+            return Err(Error::from(SetupError::DuplicateStage(name)).into());
+        }
+
+        stages.set(name, data)?;
 
         Ok(())
     })?;
 
-    globals.set("host", host_fn)?;
+    globals.set("stage", stage_fn)?;
+
+    Ok(())
+}
+
+fn add_host(lua: &Lua, name: String, data: LuaTable) -> Result<(), Error> {
+    let hosts = lua.globals().get::<LuaTable>("hosts")?;
+    if hosts.contains_key(name.as_str())? {
+        return Err(SetupError::DuplicateHost(name).into());
+    }
+
+    hosts.set(name, data)?;
 
     Ok(())
 }
@@ -147,20 +327,204 @@ fn remove_setup_functions(lua: &Lua) -> Result<(), LuaError> {
     let globals = lua.globals();
     globals.raw_remove("default")?;
     globals.raw_remove("host")?;
+    globals.raw_remove("stage")?;
+    lists::remove_setup_functions(lua)?;
+    inventory::remove_setup_functions(lua)?;
+
+    Ok(())
+}
+
+/// Merges every host over the configuration of the host it extends, before
+/// the defaults or stage are applied
+fn resolve_extends(lua: &Lua, hosts: &LuaTable) -> Result<HashMap<String, LuaTable>, Error> {
+    let mut resolved = HashMap::new();
+
+    for pair in hosts.pairs::<String, LuaTable>() {
+        let (name, _) = pair?;
+        resolve_host(lua, hosts, &name, &mut Vec::new(), &mut resolved)?;
+    }
+
+    Ok(resolved)
+}
+
+fn resolve_host(
+    lua: &Lua,
+    hosts: &LuaTable,
+    name: &str,
+    chain: &mut Vec<String>,
+    resolved: &mut HashMap<String, LuaTable>,
+) -> Result<LuaTable, Error> {
+    if let Some(host) = resolved.get(name) {
+        return Ok(host.clone());
+    }
+
+    if chain.iter().any(|host| host == name) {
+        chain.push(name.to_string());
+        SetupError::ExtendsCycle(chain.clone())?
+    }
+
+    let host = hosts.get::<LuaTable>(name)?;
+    let host = match host.get::<Option<String>>("extends")? {
+        Some(parent) => {
+            if !hosts.contains_key(parent.as_str())? {
+                let names = hosts
+                    .pairs::<String, Value>()
+                    .map(|pair| pair.map(|(name, _)| name))
+                    .collect::<Result<Vec<String>, LuaError>>()?;
+
+                return Err(SetupError::UnknownExtendedHost {
+                    host: name.to_string(),
+                    suggestion: suggestion::closest(&parent, names.iter().map(String::as_str))
+                        .map(str::to_string),
+                    extends: parent,
+                }
+                .into());
+            }
+
+            chain.push(name.to_string());
+            let parent = resolve_host(lua, hosts, &parent, chain, resolved)?;
+            chain.pop();
+
+            //the parent is copied so merging nested tables does not alter it
+            merge_tables(lua, &copy_table(lua, &parent)?, &host)?
+        }
+        None => host,
+    };
+
+    resolved.insert(name.to_string(), host.clone());
+    Ok(host)
+}
+
+fn copy_table(lua: &Lua, table: &LuaTable) -> Result<LuaTable, LuaError> {
+    let copy = lua.create_table()?;
+
+    for pair in table.pairs::<Value, Value>() {
+        let (key, value) = pair?;
+
+        match value {
+            Value::Table(value) if !is_recipe_key(&key) => {
+                copy.set(key, copy_table(lua, &value)?)?
+            }
+            value => copy.set(key, value)?,
+        }
+    }
+
+    copy.set_metatable(table.metatable())?;
+    Ok(copy)
+}
+
+/// Applies a host over the defaults or stage it inherits from
+fn merge_host(base: &PartialHost, mut host: PartialHost, ops: &[(&str, ListOps)]) -> PartialHost {
+    let mut merged = base.clone();
+
+    for (key, ops) in ops {
+        let (base_list, list) = match *key {
+            "labels" => (&base.labels, &mut host.labels),
+            "persistent_files" => (&base.persistent_files, &mut host.persistent_files),
+            "persistent_dirs" => (&base.persistent_dirs, &mut host.persistent_dirs),
+            key => unreachable!("`{}` is not a list", key),
+        };
+
+        *list = Some(lists::apply(base_list.clone().unwrap_or_default(), ops));
+    }
+
+    //ssh credentials are merged field by field, otherwise setting the
+    //hostname on a host would drop every ssh option from the defaults
+    if let (Some(base_ssh), Some(ssh)) = (&base.ssh, &host.ssh) {
+        let mut merged_ssh = base_ssh.clone();
+        merged_ssh.apply_some(ssh.clone());
+        host.ssh = Some(merged_ssh);
+    }
+
+    //same goes for the environment variables and custom variables
+    if let (Some(base_env), Some(env)) = (&base.env, &host.env) {
+        let mut merged_env = base_env.clone();
+        merged_env.extend(env.clone());
+        host.env = Some(merged_env);
+    }
+
+    if let (Some(base_vars), Some(vars)) = (&base.vars, &host.vars) {
+        let mut merged_vars = base_vars.clone();
+        merged_vars.extend(vars.clone());
+        host.vars = Some(merged_vars);
+    }
+
+    merged.apply_some(host);
+    merged
+}
+
+/// Typos in host tables would otherwise be silently ignored
+fn check_keys(table: &LuaTable, location: &str, keys: &[&str]) -> Result<(), Error> {
+    for pair in table.pairs::<Value, Value>() {
+        let (key, _) = pair?;
+        let key = match key {
+            Value::String(key) => key.to_str()?.to_string(),
+            key => key.to_string()?,
+        };
+
+        if !keys.contains(&key.as_str()) {
+            SetupError::UnknownKey {
+                location: location.to_string(),
+                suggestion: suggestion::closest(&key, keys.iter().copied()).map(str::to_string),
+                key,
+            }?
+        }
+    }
 
     Ok(())
 }
 
-fn merge_tables(a: &LuaTable, b: &LuaTable) -> Result<LuaTable, LuaError> {
+/// Nested variables are flattened with dotted keys so they can be merged key
+/// by key with the defaults and referenced as `{{php.bin}}`
+fn flatten_vars(table: &LuaTable, prefix: &str) -> Result<BTreeMap<String, String>, LuaError> {
+    let mut vars = BTreeMap::new();
+
+    for pair in table.pairs::<Value, Value>() {
+        let (key, value) = pair?;
+        let key = format!("{}{}", prefix, key.to_string()?);
+
+        match value {
+            Value::Table(table) => vars.extend(flatten_vars(&table, &format!("{}.", key))?),
+            value => {
+                vars.insert(key, value.to_string()?);
+            }
+        }
+    }
+
+    Ok(vars)
+}
+
+fn merge_tables(lua: &Lua, a: &LuaTable, b: &LuaTable) -> Result<LuaTable, LuaError> {
     let out = a.clone();
 
     for pair in b.pairs::<String, Value>() {
         let (key, b_value) = pair?;
         let key_ref = key.as_str();
 
+        //read before being overwritten so nested tables can be merged
+        let a_value = out.get::<Value>(key_ref)?;
         out.set(key_ref, &b_value)?;
 
-        if !b_value.is_table() {
+        //list operations apply to the inherited list, or are kept as they
+        //are until there is one to apply them to
+        if let Some(b_ops) = lists::list_ops(&b_value)? {
+            match (lists::list_ops(&a_value)?, a_value) {
+                (Some(mut a_ops), _) => {
+                    a_ops.extend(b_ops);
+                    out.set(key_ref, lists::to_lua(lua, &a_ops)?)?;
+                }
+                (None, Value::Table(a_table)) => {
+                    let list =
+                        lists::apply(a_table.sequence_values().collect::<Result<_, _>>()?, &b_ops);
+                    out.set(key_ref, list)?;
+                }
+                (None, _) => {}
+            }
+
+            continue;
+        }
+
+        if !b_value.is_table() || key_ref == "recipe" {
             continue;
         }
 
@@ -169,7 +533,6 @@ fn merge_tables(a: &LuaTable, b: &LuaTable) -> Result<LuaTable, LuaError> {
             continue;
         }
 
-        let a_value = out.get::<Value>(key_ref)?;
         if !a_value.is_table() {
             continue;
         }
@@ -180,12 +543,17 @@ fn merge_tables(a: &LuaTable, b: &LuaTable) -> Result<LuaTable, LuaError> {
         }
 
         //both values are table so we can merge them
-        out.set(key_ref, merge_tables(a_table, b_table)?)?;
+        out.set(key_ref, merge_tables(lua, a_table, b_table)?)?;
     }
 
     Ok(out)
 }
 
+/// Recipe classes are user tables, they are neither copied nor merged
+fn is_recipe_key(key: &Value) -> bool {
+    matches!(key, Value::String(key) if key == "recipe")
+}
+
 fn is_array(table: &LuaTable) -> bool {
     let mut i = 1;
     for pair in table.pairs::<Value, Value>() {
@@ -203,25 +571,50 @@ fn is_array(table: &LuaTable) -> bool {
     i == len + 1
 }
 
+/// Either a class, whose `new()` method is called with the class as `self`,
+/// or a function returning the recipe object
 #[derive(Debug)]
-struct LuaFunction(Function);
+enum LuaRecipe {
+    Class(LuaTable),
+    Constructor(Function),
+}
 
-impl Callable for LuaFunction {
-    fn call(&self) -> Result<(), Error> {
-        Ok(self.0.call(())?)
+impl LuaRecipe {
+    fn instantiate(&self) -> Result<Value, LuaError> {
+        match self {
+            LuaRecipe::Class(class) => match class.get::<Value>("new")? {
+                Value::Function(new) => new.call::<Value>(class),
+                _ => Err(LuaError::runtime("recipe class has no `new` method")),
+            },
+            LuaRecipe::Constructor(constructor) => constructor.call::<Value>(()),
+        }
     }
 }
 
-impl FromLua for LuaFunction {
+impl Callable for LuaRecipe {
+    fn call(&self, _: &Context) -> Result<(), Error> {
+        self.instantiate()?;
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl FromLua for LuaRecipe {
     fn from_lua(value: Value, _: &Lua) -> mlua::Result<Self> {
-        if let Value::Function(func) = value {
-            Ok(LuaFunction(func))
-        } else {
-            Err(mlua::Error::FromLuaConversionError {
+        match value {
+            Value::Table(class) => Ok(LuaRecipe::Class(class)),
+            Value::Function(constructor) => Ok(LuaRecipe::Constructor(constructor)),
+            value => Err(mlua::Error::FromLuaConversionError {
                 from: value.type_name(),
-                to: String::from("function"),
-                message: Some(format!("Expected function, got {}", value.type_name())),
-            })
+                to: String::from("recipe"),
+                message: Some(format!(
+                    "Expected a class or a function, got {}",
+                    value.type_name()
+                )),
+            }),
         }
     }
 }
@@ -231,6 +624,7 @@ impl TryFrom<LuaTable> for PartialHost {
 
     fn try_from(value: LuaTable) -> Result<Self, Self::Error> {
         let ssh = PartialSshCredentials {
+            alias: value.get::<Option<String>>("ssh_alias")?,
             hostname: value.get::<Option<String>>("hostname")?,
             port: value.get::<Option<u16>>("port")?,
             user: value.get::<Option<String>>("user")?,
@@ -244,19 +638,55 @@ impl TryFrom<LuaTable> for PartialHost {
             } else {
                 None
             },
+            connect_timeout: value
+                .get::<Option<u64>>("connect_timeout")?
+                .map(Duration::from_secs),
+            keepalive_interval: value
+                .get::<Option<u64>>("keepalive_interval")?
+                .map(Duration::from_secs),
+            connect_retries: value.get::<Option<u8>>("connect_retries")?,
         };
 
         Ok(PartialHost {
             recipe: value
-                .get::<Option<LuaFunction>>("recipe")?
-                .map(|lua_fn| Rc::new(lua_fn) as Rc<dyn Callable>),
+                .get::<Option<LuaRecipe>>("recipe")?
+                .map(|recipe| Rc::new(recipe) as Rc<dyn Callable>),
             repository: value.get::<Option<String>>("repository")?,
             keep_releases: value.get::<Option<i8>>("keep_releases")?,
-            persistent_files: value.get::<Option<Vec<String>>>("persistent_files")?,
-            persistent_dirs: value.get::<Option<Vec<String>>>("persistent_dirs")?,
-            labels: value.get::<Option<Vec<String>>>("labels")?,
+            persistent_files: lists::get(&value, "persistent_files")?,
+            persistent_dirs: lists::get(&value, "persistent_dirs")?,
+            labels: lists::get(&value, "labels")?,
+            env: value.get::<Option<BTreeMap<String, String>>>("env")?,
+            vars: value
+                .get::<Option<LuaTable>>("vars")?
+                .map(|vars| flatten_vars(&vars, ""))
+                .transpose()?,
             ssh: if !ssh.is_empty() { Some(ssh) } else { None },
             path: value.get::<Option<String>>("path")?,
+            stage: value.get::<Option<String>>("stage")?,
+            become_user: match value.get::<Become>("become")? {
+                Become::Inherit => None,
+                become_user => Some(become_user),
+            },
+            become_method: value
+                .get::<Option<String>>("become_method")?
+                .map(|method| method.parse::<BecomeMethod>())
+                .transpose()?,
+            become_password: value.get::<Option<String>>("become_password")?,
+            strategy: value
+                .get::<Option<String>>("strategy")?
+                .map(|strategy| strategy.parse::<Strategy>())
+                .transpose()?,
+            //numbers are converted to strings so `2` and `"25%"` share a parser
+            batch_size: value
+                .get::<Option<String>>("batch_size")?
+                .map(|size| size.parse::<BatchSize>())
+                .transpose()?,
+            batch_pause: value
+                .get::<Option<u64>>("batch_pause")?
+                .map(Duration::from_secs),
+            rollback_on_failure: value.get::<Option<bool>>("rollback_on_failure")?,
+            auto_rollback: value.get::<Option<bool>>("auto_rollback")?,
         })
     }
 }
@@ -0,0 +1,153 @@
+use crate::access::Access;
+use crate::error::{Error, SetupError};
+use mlua::prelude::LuaError;
+use mlua::{Lua, LuaSerdeExt, SerializeOptions, Value};
+use serde_json::Value as JsonValue;
+use std::fs;
+use std::path::PathBuf;
+
+const SETUP_FUNCTIONS: [&str; 3] = ["inventory_json", "inventory_toml", "inventory_command"];
+
+#[derive(Clone, Copy, Debug)]
+enum Format {
+    Json,
+    Toml,
+}
+
+/// Registers the functions adding hosts from records generated outside of
+/// the script, the records use the same keys as `host()`. The commands of
+/// `inventory_command()` run whenever the hosts are listed, including dry
+/// runs and `ettac hosts`, unless `run_commands` is false. Paths and commands
+/// are relative to `dir`, the directory of the script.
+pub fn add_setup_functions(lua: &Lua, dir: PathBuf, run_commands: bool) -> Result<(), LuaError> {
+    let globals = lua.globals();
+
+    let json_dir = dir.clone();
+    let inventory_json = lua.create_function(move |lua, (path,): (String,)| {
+        Ok(add_hosts(
+            lua,
+            &path,
+            &fs::read_to_string(json_dir.join(&path))?,
+            Format::Json,
+        )?)
+    })?;
+
+    globals.set("inventory_json", inventory_json)?;
+
+    let toml_dir = dir.clone();
+    let inventory_toml = lua.create_function(move |lua, (path,): (String,)| {
+        Ok(add_hosts(
+            lua,
+            &path,
+            &fs::read_to_string(toml_dir.join(&path))?,
+            Format::Toml,
+        )?)
+    })?;
+
+    globals.set("inventory_toml", inventory_toml)?;
+
+    let inventory_command =
+        lua.create_function(move |lua, (command, format): (String, Option<String>)| {
+            let format = match format.as_deref() {
+                None | Some("json") => Format::Json,
+                Some("toml") => Format::Toml,
+                Some(format) => Err(LuaError::runtime(format!(
+                    "unknown inventory format `{}`, expected `json` or `toml`",
+                    format
+                )))?,
+            };
+
+            if !run_commands {
+                return Ok(());
+            }
+
+            let result = Access::Local(dir.to_string_lossy().to_string()).run(&command)?;
+            if result.status != 0 {
+                Err(Error::CommandFailed {
+                    command: command.clone(),
+                    status: result.status,
+                    stderr: result.stderr.trim_end().to_string(),
+                })?
+            }
+
+            Ok(add_hosts(lua, &command, &result.stdout, format)?)
+        })?;
+
+    globals.set("inventory_command", inventory_command)?;
+
+    Ok(())
+}
+
+pub fn remove_setup_functions(lua: &Lua) -> Result<(), LuaError> {
+    for name in SETUP_FUNCTIONS {
+        lua.globals().raw_remove(name)?;
+    }
+
+    Ok(())
+}
+
+fn add_hosts(lua: &Lua, origin: &str, content: &str, format: Format) -> Result<(), Error> {
+    let invalid = |reason: String| SetupError::InvalidInventory {
+        origin: origin.to_string(),
+        reason,
+    };
+
+    let document = match format {
+        Format::Json => {
+            serde_json::from_str::<JsonValue>(content).map_err(|err| invalid(err.to_string()))?
+        }
+        Format::Toml => {
+            let document =
+                toml::from_str::<toml::Table>(content).map_err(|err| invalid(err.to_string()))?;
+
+            serde_json::to_value(document).map_err(|err| invalid(err.to_string()))?
+        }
+    };
+
+    //null values are nil so they behave like absent keys
+    let options = SerializeOptions::new()
+        .serialize_none_to_null(false)
+        .serialize_unit_to_null(false)
+        .set_array_metatable(false);
+
+    for (name, record) in records(document).map_err(invalid)? {
+        let Value::Table(record) = lua.to_value_with(&record, options)? else {
+            return Err(invalid(format!("host `{}` is not a table", name)).into());
+        };
+
+        super::add_host(lua, name, record)?;
+    }
+
+    Ok(())
+}
+
+/// Hosts are either an object keyed by host name or an array of records with
+/// a `name` key, optionally nested in a top level `hosts` key
+fn records(document: JsonValue) -> Result<Vec<(String, JsonValue)>, String> {
+    let hosts = match document {
+        JsonValue::Object(mut document) if document.contains_key("hosts") => {
+            document.remove("hosts").expect("hosts key exists")
+        }
+        document => document,
+    };
+
+    match hosts {
+        JsonValue::Object(hosts) => Ok(hosts.into_iter().collect()),
+        JsonValue::Array(hosts) => hosts
+            .into_iter()
+            .map(|record| {
+                let JsonValue::Object(mut record) = record else {
+                    return Err(String::from("host records must be objects"));
+                };
+
+                match record.remove("name") {
+                    Some(JsonValue::String(name)) => Ok((name, JsonValue::Object(record))),
+                    _ => Err(String::from("host records must have a `name` string")),
+                }
+            })
+            .collect(),
+        _ => Err(String::from(
+            "expected an object of hosts or an array of host records",
+        )),
+    }
+}
@@ -0,0 +1,151 @@
+use mlua::prelude::{LuaError, LuaTable};
+use mlua::{Lua, Value};
+
+pub const LIST_KEYS: [&str; 3] = ["labels", "persistent_files", "persistent_dirs"];
+const SETUP_FUNCTIONS: [&str; 3] = ["extend", "prepend", "remove"];
+const MARKER: &str = "__list_operations";
+
+/// Change applied to an inherited list instead of replacing it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ListOp {
+    Extend,
+    Prepend,
+    Remove,
+}
+
+pub type ListOps = Vec<(ListOp, Vec<String>)>;
+
+impl ListOp {
+    fn name(self) -> &'static str {
+        match self {
+            ListOp::Extend => "extend",
+            ListOp::Prepend => "prepend",
+            ListOp::Remove => "remove",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "extend" => Some(ListOp::Extend),
+            "prepend" => Some(ListOp::Prepend),
+            "remove" => Some(ListOp::Remove),
+            _ => None,
+        }
+    }
+}
+
+/// Registers `extend`, `prepend` and `remove` which mark a list as a change
+/// to the inherited one, e.g. `persistent_dirs = extend { "var/cache" }`
+pub fn add_setup_functions(lua: &Lua) -> Result<(), LuaError> {
+    for name in SETUP_FUNCTIONS {
+        let op = ListOp::from_name(name).expect("setup functions are operations");
+        let function = lua.create_function(move |lua, (values,): (Vec<String>,)| {
+            to_lua(lua, &vec![(op, values)])
+        })?;
+
+        lua.globals().set(name, function)?;
+    }
+
+    Ok(())
+}
+
+pub fn remove_setup_functions(lua: &Lua) -> Result<(), LuaError> {
+    for name in SETUP_FUNCTIONS {
+        lua.globals().raw_remove(name)?;
+    }
+
+    Ok(())
+}
+
+/// Operations of a value created by one of the helpers, `None` for any other
+/// value
+pub fn list_ops(value: &Value) -> Result<Option<ListOps>, LuaError> {
+    let Value::Table(table) = value else {
+        return Ok(None);
+    };
+
+    let is_marked = match table.metatable() {
+        Some(metatable) => metatable.raw_get::<bool>(MARKER)?,
+        None => false,
+    };
+
+    if !is_marked {
+        return Ok(None);
+    }
+
+    let mut ops = Vec::new();
+    for entry in table.sequence_values::<LuaTable>() {
+        let entry = entry?;
+        let name = entry.get::<String>(1)?;
+        let op = ListOp::from_name(&name)
+            .ok_or_else(|| LuaError::runtime(format!("unknown list operation `{}`", name)))?;
+
+        ops.push((op, entry.get::<Vec<String>>(2)?));
+    }
+
+    Ok(Some(ops))
+}
+
+/// List of a host table, operations without an inherited list to apply to
+/// apply to an empty one
+pub fn get(table: &LuaTable, key: &str) -> Result<Option<Vec<String>>, LuaError> {
+    match list_ops(&table.get::<Value>(key)?)? {
+        Some(ops) => Ok(Some(apply(Vec::new(), &ops))),
+        None => table.get::<Option<Vec<String>>>(key),
+    }
+}
+
+/// Operations of the lists of a host, stage or defaults table
+pub fn layer_ops(table: &LuaTable) -> Result<Vec<(&'static str, ListOps)>, LuaError> {
+    let mut layer_ops = Vec::new();
+    for key in LIST_KEYS {
+        if let Some(ops) = list_ops(&table.get::<Value>(key)?)? {
+            layer_ops.push((key, ops));
+        }
+    }
+
+    Ok(layer_ops)
+}
+
+pub fn to_lua(lua: &Lua, ops: &ListOps) -> Result<LuaTable, LuaError> {
+    let table = lua.create_table()?;
+    for (op, values) in ops {
+        table.push(lua.create_sequence_from([
+            Value::String(lua.create_string(op.name())?),
+            Value::Table(lua.create_sequence_from(values.iter().map(String::as_str))?),
+        ])?)?;
+    }
+
+    let metatable = lua.create_table()?;
+    metatable.raw_set(MARKER, true)?;
+
+    table.set_metatable(Some(metatable))?;
+    Ok(table)
+}
+
+pub fn apply(mut list: Vec<String>, ops: &ListOps) -> Vec<String> {
+    for (op, values) in ops {
+        match op {
+            ListOp::Extend => {
+                for value in values {
+                    if !list.contains(value) {
+                        list.push(value.clone());
+                    }
+                }
+            }
+            ListOp::Prepend => {
+                let mut prepended = values
+                    .iter()
+                    .filter(|value| !list.contains(value))
+                    .cloned()
+                    .collect::<Vec<String>>();
+
+                prepended.append(&mut list);
+                list = prepended;
+            }
+            ListOp::Remove => list.retain(|value| !values.contains(value)),
+        }
+    }
+
+    list
+}
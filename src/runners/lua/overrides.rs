@@ -0,0 +1,168 @@
+use super::HOST_KEYS;
+use crate::context::{BatchSize, BecomeMethod, Strategy};
+use crate::error::{Error, SetupError};
+use crate::suggestion;
+use mlua::prelude::LuaTable;
+use mlua::{Lua, Value};
+
+/// Keys resolved before the overrides are applied
+const FIXED_KEYS: [&str; 3] = ["recipe", "extends", "stage"];
+
+/// Builds a host table from the `-o key=value` options, values are parsed to
+/// the type of the key so mistakes are reported before anything runs
+pub fn to_table(lua: &Lua, overrides: &[String]) -> Result<LuaTable, Error> {
+    let table = lua.create_table()?;
+
+    for option in overrides {
+        let Some((key, value)) = option.split_once('=') else {
+            return Err(SetupError::InvalidOverride {
+                key: option.clone(),
+                reason: String::from("expected `key=value`"),
+            }
+            .into());
+        };
+
+        let (key, name) = match key.split_once('.') {
+            Some((key, name)) => (key.trim(), Some(name.trim())),
+            None => (key.trim(), None),
+        };
+
+        let invalid = |reason: &str| SetupError::InvalidOverride {
+            key: key.to_string(),
+            reason: reason.to_string(),
+        };
+
+        let overridable = HOST_KEYS
+            .into_iter()
+            .filter(|key| !FIXED_KEYS.contains(key))
+            .collect::<Vec<&str>>();
+
+        if FIXED_KEYS.contains(&key) {
+            Err(invalid("can not be overridden from the command line"))?
+        }
+
+        if !overridable.contains(&key) {
+            Err(SetupError::UnknownKey {
+                location: String::from("overrides"),
+                key: key.to_string(),
+                suggestion: suggestion::closest(key, overridable).map(str::to_string),
+            })?
+        }
+
+        let value = match (key, name) {
+            ("env" | "vars", Some(name)) if !name.is_empty() => {
+                let variables = match table.get::<Option<LuaTable>>(key)? {
+                    Some(variables) => variables,
+                    None => lua.create_table()?,
+                };
+
+                match key {
+                    "env" => variables.set(name, value)?,
+                    _ => set_path(lua, &variables, name, value)?,
+                }
+
+                Value::Table(variables)
+            }
+            ("env" | "vars", _) => {
+                Err(invalid("expected a variable name, e.g. `vars.name=value`"))?
+            }
+            (_, Some(_)) => Err(invalid("only `env` and `vars` have nested keys"))?,
+            ("keep_releases", None) => Value::Integer(
+                parse::<i8>(value)
+                    .ok_or_else(|| invalid("expected a number between -128 and 127"))?
+                    .into(),
+            ),
+            ("port", None) => Value::Integer(
+                parse::<u16>(value)
+                    .ok_or_else(|| invalid("expected a port number"))?
+                    .into(),
+            ),
+            ("connect_retries", None) => Value::Integer(
+                parse::<u8>(value)
+                    .ok_or_else(|| invalid("expected a number between 0 and 255"))?
+                    .into(),
+            ),
+            ("connect_timeout" | "keepalive_interval" | "batch_pause", None) => Value::Integer(
+                parse::<u32>(value)
+                    .ok_or_else(|| invalid("expected a number of seconds"))?
+                    .into(),
+            ),
+            ("labels" | "persistent_files" | "persistent_dirs", None) => Value::Table(
+                lua.create_sequence_from(
+                    value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|value| !value.is_empty()),
+                )?,
+            ),
+            ("rollback_on_failure" | "auto_rollback", None) => Value::Boolean(
+                parse::<bool>(value).ok_or_else(|| invalid("expected `true` or `false`"))?,
+            ),
+            ("become", None) => match value.trim() {
+                "" | "false" => Value::Boolean(false),
+                user if is_user_name(user) => Value::String(lua.create_string(user)?),
+                _ => Err(invalid(
+                    "expected a user name, or `false` or an empty value to disable become",
+                ))?,
+            },
+            ("become_method", None) => {
+                value
+                    .parse::<BecomeMethod>()
+                    .map_err(|err| invalid(&err.to_string()))?;
+                Value::String(lua.create_string(value)?)
+            }
+            ("strategy", None) => {
+                value
+                    .parse::<Strategy>()
+                    .map_err(|err| invalid(&err.to_string()))?;
+                Value::String(lua.create_string(value)?)
+            }
+            ("batch_size", None) => {
+                value
+                    .parse::<BatchSize>()
+                    .map_err(|err| invalid(&err.to_string()))?;
+                Value::String(lua.create_string(value)?)
+            }
+            (_, None) => Value::String(lua.create_string(value)?),
+        };
+
+        table.set(key, value)?;
+    }
+
+    Ok(table)
+}
+
+/// Names accepted by `useradd`, they may end with `$` for machine accounts
+fn is_user_name(name: &str) -> bool {
+    let name = name.strip_suffix('$').unwrap_or(name);
+
+    !name.is_empty()
+        && !name.starts_with('-')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+}
+
+fn parse<T: std::str::FromStr>(value: &str) -> Option<T> {
+    value.trim().parse::<T>().ok()
+}
+
+/// Dotted names are nested tables so they are flattened like the `vars` of
+/// the script
+fn set_path(lua: &Lua, table: &LuaTable, name: &str, value: &str) -> Result<(), Error> {
+    match name.split_once('.') {
+        Some((key, rest)) => {
+            let child = match table.get::<Option<LuaTable>>(key)? {
+                Some(child) => child,
+                None => {
+                    let child = lua.create_table()?;
+                    table.set(key, &child)?;
+                    child
+                }
+            };
+
+            set_path(lua, &child, rest, value)
+        }
+        None => Ok(table.set(name, value)?),
+    }
+}
@@ -0,0 +1,691 @@
+use crate::access::CommandLine;
+use crate::context::{Become, Callable, Condition, Context};
+use crate::error::Error;
+use crate::library::{self, RunOptions};
+use crate::tasks::{Phase, Task, TaskGraph, TaskOptions};
+use mlua::prelude::{LuaError, LuaTable};
+use mlua::{FromLua, Function, IntoLua, Lua, Scope, Value};
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::time::Duration;
+
+const DESCRIBE_FUNCTIONS: [&str; 6] = ["task", "use", "after", "remove", "wrap", "catch"];
+const TASK_FUNCTIONS: [&str; 7] = [
+    "remote",
+    "run_locally",
+    "send",
+    "set_timeout",
+    "continue",
+    "deploy",
+    "symlink",
+];
+
+/// Registry key of the set of classes defined by the library modules
+pub const BUILTIN_MODULES: &str = "ettac.builtin_modules";
+/// Registry key of the source of the script and the library modules by chunk
+/// name, only set during a dry run
+pub const SOURCES: &str = "ettac.sources";
+
+/// A module whose `describe()` method is being called
+#[derive(Clone)]
+struct Module {
+    origin: String,
+    builtin: bool,
+    object: LuaTable,
+    /// Given as `self` to the methods of the module, see [`proxy`]
+    proxy: LuaTable,
+}
+
+impl Module {
+    fn new(lua: &Lua, origin: String, builtin: bool, object: LuaTable) -> Result<Self, LuaError> {
+        Ok(Module {
+            origin,
+            builtin,
+            proxy: proxy(lua, &object)?,
+            object,
+        })
+    }
+}
+
+/// Calls the recipe's `describe()` method and collects the tasks declared
+/// by the recipe and the modules it uses
+pub fn describe(lua: &Lua, recipe: LuaTable) -> Result<TaskGraph, Error> {
+    let graph = RefCell::new(TaskGraph::default());
+    let modules = RefCell::new(Vec::<Module>::new());
+    let stack = RefCell::new(Vec::<Module>::new());
+
+    let describe_module = |module: Module| -> Result<(), LuaError> {
+        let describe = method(&module.proxy, "describe")?;
+
+        modules.borrow_mut().push(module.clone());
+        stack.borrow_mut().push(module.clone());
+        let result = describe.call::<()>(&module.proxy);
+        stack.borrow_mut().pop();
+
+        result
+    };
+
+    lua.scope(|scope| {
+        let globals = lua.globals();
+
+        let task_fn =
+            scope.create_function(|_, (function, options): (Function, Option<LuaTable>)| {
+                let module =
+                    stack.borrow().last().cloned().ok_or_else(|| {
+                        LuaError::runtime("task() must be called from describe()")
+                    })?;
+
+                let task = new_task(lua, &modules.borrow(), Some(&module), function, options)?;
+                graph.borrow_mut().push(task);
+
+                Ok(())
+            })?;
+
+        let use_fn = scope.create_function(|lua, (object,): (LuaTable,)| {
+            let origin = module_name(lua, &object);
+            let builtin = is_builtin(lua, &object)?;
+
+            describe_module(Module::new(lua, origin, builtin, object)?)
+        })?;
+
+        let after_fn = scope.create_function(
+            |_, (anchor, function, options): (Function, Function, Option<LuaTable>)| {
+                let mut graph = graph.borrow_mut();
+                let modules = modules.borrow();
+                find_task(&graph, &modules, &anchor)?;
+
+                let mut task = match position(&graph, &function) {
+                    Some(index) => graph.remove(index),
+                    None => new_task(lua, &modules, None, function, options)?,
+                };
+
+                //tasks run after their anchor so they share its phase
+                let anchor_index = find_task(&graph, &modules, &anchor)?;
+                if let Some(anchor) = graph.get(anchor_index) {
+                    task.options.phase = anchor.options.phase;
+                    task.after = Some(anchor.key());
+                }
+
+                graph.insert_after(anchor_index, task);
+
+                Ok(())
+            },
+        )?;
+
+        let remove_fn = scope.create_function(|_, (function,): (Function,)| {
+            let mut graph = graph.borrow_mut();
+            let index = find_task(&graph, &modules.borrow(), &function)?;
+            graph.remove(index);
+
+            Ok(())
+        })?;
+
+        let wrap_fn = scope.create_function(|_, (function, wrapper): (Function, Function)| {
+            update_task(&graph, &modules.borrow(), &function, |task| {
+                task.wrappers.push(wrapper)
+            })
+        })?;
+
+        let catch_fn = scope.create_function(|_, (function, handler): (Function, Function)| {
+            update_task(&graph, &modules.borrow(), &function, |task| {
+                task.handler = Some(handler)
+            })
+        })?;
+
+        for (name, function) in DESCRIBE_FUNCTIONS
+            .into_iter()
+            .zip([task_fn, use_fn, after_fn, remove_fn, wrap_fn, catch_fn])
+        {
+            globals.set(name, function)?;
+        }
+
+        let recipe = Module::new(lua, String::from("recipe"), false, recipe)?;
+        if method(&recipe.proxy, "describe").is_err() {
+            Err(LuaError::runtime(
+                "recipe has no `describe` method, classes whose `new()` expects `self` are given as `recipe = Class`",
+            ))?
+        }
+
+        let result = describe_module(recipe);
+
+        for name in DESCRIBE_FUNCTIONS {
+            globals.raw_remove(name)?;
+        }
+
+        result
+    })?;
+
+    Ok(graph.into_inner())
+}
+
+/// Task declared in Lua, called with the module that declared it as `self`
+#[derive(Clone, Debug)]
+pub struct LuaTask {
+    lua: Lua,
+    object: LuaTable,
+    function: Function,
+    wrappers: Vec<Function>,
+    handler: Option<Function>,
+}
+
+impl Callable for LuaTask {
+    fn call(&self, ctx: &Context) -> Result<(), Error> {
+        let lua = &self.lua;
+
+        let continued = Cell::new(false);
+
+        lua.scope(|scope| {
+            register_task_functions(lua, scope, ctx, &continued)?;
+
+            let mut call = scope.create_function(|_, ()| self.function.call::<()>(&self.object))?;
+            for wrapper in &self.wrappers {
+                let inner = call.clone();
+                call = scope.create_function(move |_, ()| wrapper.call::<()>(&inner))?;
+            }
+
+            let result = match (call.call::<()>(()), &self.handler) {
+                (Err(err), Some(handler)) => match handler.call::<()>(err.to_string()) {
+                    Ok(()) if continued.get() => Ok(()),
+                    Ok(()) => Err(err),
+                    Err(handler_err) => Err(handler_err),
+                },
+                (result, _) => result,
+            };
+
+            remove_task_functions(lua)?;
+
+            result
+        })?;
+
+        Ok(())
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// Predicate of the `when` option, called with the `deploy` table and able
+/// to run commands like a task
+#[derive(Debug)]
+struct LuaCondition {
+    lua: Lua,
+    function: Function,
+}
+
+impl Condition for LuaCondition {
+    fn test(&self, ctx: &Context) -> Result<bool, Error> {
+        let lua = &self.lua;
+        let continued = Cell::new(false);
+
+        let holds = lua.scope(|scope| {
+            register_task_functions(lua, scope, ctx, &continued)?;
+            let result = self
+                .function
+                .call::<bool>(lua.globals().get::<LuaTable>("deploy")?);
+            remove_task_functions(lua)?;
+
+            result
+        })?;
+
+        Ok(holds)
+    }
+}
+
+fn remove_task_functions(lua: &Lua) -> Result<(), LuaError> {
+    let globals = lua.globals();
+    for name in TASK_FUNCTIONS {
+        globals.raw_remove(name)?;
+    }
+
+    Ok(())
+}
+
+fn register_task_functions<'scope>(
+    lua: &Lua,
+    scope: &'scope Scope<'scope, '_>,
+    ctx: &'scope Context,
+    continued: &'scope Cell<bool>,
+) -> Result<(), LuaError> {
+    let globals = lua.globals();
+
+    let remote = scope.create_function(|lua, (command, options): (Value, Option<LuaTable>)| {
+        let command = command_line(command, options.as_ref())?;
+        let output = library::remote(ctx, &command, run_options(options)?)?;
+
+        command_output(lua, ctx, output)
+    })?;
+
+    let run_locally =
+        scope.create_function(|lua, (command, options): (Value, Option<LuaTable>)| {
+            let command = command_line(command, options.as_ref())?;
+            let output = library::local(ctx, &command, run_options(options)?)?;
+
+            command_output(lua, ctx, output)
+        })?;
+
+    let symlink = scope.create_function(|_, (target, link): (String, String)| {
+        Ok(library::symlink(ctx, &target, &link)?)
+    })?;
+
+    let send = scope.create_function(|_, (from, dest): (String, Option<String>)| {
+        Ok(library::send(ctx, &from, dest.as_deref())?)
+    })?;
+
+    let set_timeout = scope.create_function(|_, (timeout,): (i32,)| {
+        library::set_timeout(ctx, timeout);
+        Ok(())
+    })?;
+
+    let continue_fn = scope.create_function(|_, ()| {
+        continued.set(true);
+        Ok(())
+    })?;
+
+    let deploy = lua.create_table()?;
+    deploy.set("path", ctx.host.path.as_str())?;
+    deploy.set("release", ctx.release.as_str())?;
+    deploy.set("release_path", ctx.release_path())?;
+    deploy.set("discard_release", ctx.discard_release)?;
+    deploy.set("shared_path", ctx.shared_path())?;
+    deploy.set("current_path", ctx.current_path())?;
+    deploy.set("repository", ctx.host.repository.as_str())?;
+    deploy.set("keep_releases", ctx.host.keep_releases)?;
+    deploy.set("persistent_files", ctx.host.persistent_files.clone())?;
+    deploy.set("persistent_dirs", ctx.host.persistent_dirs.clone())?;
+    deploy.set("labels", ctx.host.labels.clone())?;
+    deploy.set("env", ctx.host.env.clone())?;
+    deploy.set("vars", nested_vars(lua, &ctx.host.vars)?)?;
+
+    globals.set("remote", remote)?;
+    globals.set("run_locally", run_locally)?;
+    globals.set("send", send)?;
+    globals.set("symlink", symlink)?;
+    globals.set("set_timeout", set_timeout)?;
+    globals.set("continue", continue_fn)?;
+    globals.set("deploy", deploy)?;
+
+    Ok(())
+}
+
+/// Variables are stored flattened with dotted keys, they are given back their
+/// original shape for the tasks
+fn nested_vars(lua: &Lua, vars: &BTreeMap<String, String>) -> Result<LuaTable, LuaError> {
+    let root = lua.create_table()?;
+
+    for (name, value) in vars {
+        let mut table = root.clone();
+        let mut keys = name.split('.').peekable();
+
+        while let Some(key) = keys.next() {
+            if keys.peek().is_none() {
+                table.set(key, value.as_str())?;
+                break;
+            }
+
+            table = match table.get::<Option<LuaTable>>(key)? {
+                Some(child) => child,
+                None => {
+                    let child = lua.create_table()?;
+                    table.set(key, &child)?;
+                    child
+                }
+            };
+        }
+    }
+
+    Ok(root)
+}
+
+/// During a dry run the output is empty, the plan then warns when the script
+/// uses it as the next steps may differ from the real deploy
+fn command_output(lua: &Lua, ctx: &Context, output: String) -> Result<Value, LuaError> {
+    if let Some(plan) = &ctx.plan {
+        if output_used(lua)? {
+            plan.output_read(plan.last_step());
+        }
+
+        return "".into_lua(lua);
+    }
+
+    output.into_lua(lua)
+}
+
+/// Whether the output of the command being run is used, which is the case
+/// unless the line calling the command starts with the call. The caller is the
+/// first function of the script or the library modules up the stack, so calls
+/// through the module proxies are seen from the task. The output is assumed
+/// to be used when the line can not be found
+fn output_used(lua: &Lua) -> Result<bool, LuaError> {
+    let Some(sources) = lua.named_registry_value::<Option<LuaTable>>(SOURCES)? else {
+        return Ok(true);
+    };
+
+    for level in 1.. {
+        let Some(caller) = lua.inspect_stack(level, |caller| {
+            let chunk = caller.source().source.map(|source| source.into_owned());
+            (chunk, caller.current_line())
+        }) else {
+            break;
+        };
+
+        let (Some(chunk), Some(line)) = caller else {
+            continue;
+        };
+
+        let Some(source) = sources.get::<Option<String>>(chunk)? else {
+            continue;
+        };
+
+        let discarded = source
+            .lines()
+            .nth(line.saturating_sub(1))
+            .map(str::trim_start)
+            .is_some_and(|line| {
+                ["remote(", "run_locally(", "self("]
+                    .iter()
+                    .any(|call| line.starts_with(call))
+            });
+
+        return Ok(!discarded);
+    }
+
+    Ok(true)
+}
+
+/// Strings run through `sh -c` unless `shell = false` is given, in which
+/// case they are split into arguments, tables are always run as arguments
+fn command_line(command: Value, options: Option<&LuaTable>) -> Result<CommandLine, LuaError> {
+    let shell = match options {
+        Some(options) => options.get::<Option<bool>>("shell")?.unwrap_or(true),
+        None => true,
+    };
+
+    match command {
+        Value::String(command) if shell => Ok(CommandLine::Shell(command.to_str()?.to_string())),
+        Value::String(command) => {
+            let command = command.to_str()?.to_string();
+
+            match shlex::split(&command) {
+                Some(args) => Ok(CommandLine::Argv(args)),
+                None => Err(Error::UnparseableCommand(command).into()),
+            }
+        }
+        Value::Table(args) => Ok(CommandLine::Argv(
+            args.sequence_values::<String>()
+                .collect::<Result<Vec<String>, LuaError>>()?,
+        )),
+        value => Err(LuaError::runtime(format!(
+            "command must be a string or a table, got {}",
+            value.type_name()
+        ))),
+    }
+}
+
+fn run_options(options: Option<LuaTable>) -> Result<RunOptions, LuaError> {
+    let Some(options) = options else {
+        return Ok(RunOptions::default());
+    };
+
+    Ok(RunOptions {
+        cwd: options.get::<Option<String>>("cwd")?,
+        env: options
+            .get::<Option<BTreeMap<String, String>>>("env")?
+            .unwrap_or_default(),
+        become_user: options.get::<Become>("become")?,
+    })
+}
+
+fn task_options(lua: &Lua, options: Option<&LuaTable>) -> Result<TaskOptions, LuaError> {
+    let Some(options) = options else {
+        return Ok(TaskOptions::default());
+    };
+
+    let phase = match options.get::<Option<String>>("phase")? {
+        Some(phase) => phase.parse::<Phase>().map_err(LuaError::runtime)?,
+        None => Phase::default(),
+    };
+
+    let on = match options.get::<Value>("on")? {
+        Value::Nil => Vec::new(),
+        Value::String(label) => vec![label.to_str()?.to_string()],
+        Value::Table(labels) => labels
+            .sequence_values::<String>()
+            .collect::<Result<_, _>>()?,
+        _ => Err(LuaError::runtime(
+            "`on` must be a label or a list of labels",
+        ))?,
+    };
+
+    Ok(TaskOptions {
+        phase,
+        become_user: options.get::<Become>("become")?,
+        on,
+        once: options.get::<Option<bool>>("once")?.unwrap_or_default(),
+        when: options.get::<Option<Function>>("when")?.map(|function| {
+            Rc::new(LuaCondition {
+                lua: lua.clone(),
+                function,
+            }) as Rc<dyn Condition>
+        }),
+        retries: options.get::<Option<u32>>("retries")?.unwrap_or_default(),
+        retry_delay: Duration::from_secs(
+            options
+                .get::<Option<u64>>("retry_delay")?
+                .unwrap_or_default(),
+        ),
+        timeout: options
+            .get::<Option<u64>>("timeout")?
+            .map(Duration::from_secs),
+        rerun: options.get::<Option<bool>>("rerun")?.unwrap_or_default(),
+    })
+}
+
+fn new_task(
+    lua: &Lua,
+    modules: &[Module],
+    module: Option<&Module>,
+    function: Function,
+    options: Option<LuaTable>,
+) -> Result<Task, LuaError> {
+    let owner = match module {
+        Some(module) => Some(module.clone()),
+        None => modules
+            .iter()
+            .rev()
+            .find(|module| method_name(&module.object, &function).is_some())
+            .cloned(),
+    };
+
+    let explicit_name = match &options {
+        Some(options) => options.get::<Option<String>>("name")?,
+        None => None,
+    };
+
+    let name = explicit_name
+        .or_else(|| {
+            owner
+                .as_ref()
+                .and_then(|owner| method_name(&owner.object, &function))
+        })
+        .unwrap_or_else(|| String::from("anonymous"));
+
+    let object = match &owner {
+        Some(owner) => owner.proxy.clone(),
+        None => lua.create_table()?,
+    };
+
+    Ok(Task {
+        name,
+        builtin: owner.as_ref().is_some_and(|owner| owner.builtin),
+        after: None,
+        origin: owner
+            .map(|owner| owner.origin)
+            .unwrap_or_else(|| String::from("recipe")),
+        callable: Rc::new(LuaTask {
+            lua: lua.clone(),
+            object,
+            function,
+            wrappers: vec![],
+            handler: None,
+        }),
+        options: task_options(lua, options.as_ref())?,
+    })
+}
+
+fn position(graph: &TaskGraph, function: &Function) -> Option<usize> {
+    graph.position(|task| {
+        task.callable
+            .as_any()
+            .downcast_ref::<LuaTask>()
+            .is_some_and(|task| task.function == *function)
+    })
+}
+
+fn find_task(
+    graph: &TaskGraph,
+    modules: &[Module],
+    function: &Function,
+) -> Result<usize, LuaError> {
+    position(graph, function).ok_or_else(|| {
+        let name = modules
+            .iter()
+            .find_map(|module| method_name(&module.object, function))
+            .unwrap_or_else(|| String::from("anonymous"));
+
+        LuaError::runtime(format!("task `{}` was not declared", name))
+    })
+}
+
+fn update_task(
+    graph: &RefCell<TaskGraph>,
+    modules: &[Module],
+    function: &Function,
+    update: impl FnOnce(&mut LuaTask),
+) -> Result<(), LuaError> {
+    let mut graph = graph.borrow_mut();
+    let index = find_task(&graph, modules, function)?;
+    let task = graph.get_mut(index).expect("index was just found");
+
+    let mut lua_task = task
+        .callable
+        .as_any()
+        .downcast_ref::<LuaTask>()
+        .expect("tasks are declared in Lua")
+        .clone();
+
+    update(&mut lua_task);
+    task.callable = Rc::new(lua_task);
+
+    Ok(())
+}
+
+/// Looks up a method through the object, its metatable and the `__index`
+/// chain of the metatable
+fn method(object: &LuaTable, name: &str) -> Result<Function, LuaError> {
+    match object.get::<Value>(name)? {
+        Value::Function(function) => Ok(function),
+        _ => Err(LuaError::runtime(format!("method `{}` not found", name))),
+    }
+}
+
+fn method_name(object: &LuaTable, function: &Function) -> Option<String> {
+    let mut table = Some(object.clone());
+
+    //the depth is limited in case of cyclic __index chains
+    for _ in 0..8 {
+        let current = table.take()?;
+        for (key, value) in current.pairs::<Value, Value>().flatten() {
+            if let (Value::String(key), Value::Function(value)) = (key, value)
+                && value == *function
+            {
+                return key.to_str().ok().map(|key| key.to_string());
+            }
+        }
+
+        let metatable = current.metatable()?;
+        table = match metatable.raw_get::<Value>("__index").ok()? {
+            Value::Table(index) if index != current => Some(index),
+            _ if metatable != current => Some(metatable),
+            _ => None,
+        };
+    }
+
+    None
+}
+
+/// Name of the global holding the class of the module, if any
+fn module_name(lua: &Lua, object: &LuaTable) -> String {
+    let Some(class) = object.metatable() else {
+        return String::from("module");
+    };
+
+    lua.globals()
+        .pairs::<String, Value>()
+        .flatten()
+        .find(|(_, value)| matches!(value, Value::Table(table) if *table == class))
+        .map(|(name, _)| name)
+        .unwrap_or_else(|| String::from("module"))
+}
+
+/// Whether the class of a module is one of the library modules, see
+/// [`BUILTIN_MODULES`]
+fn is_builtin(lua: &Lua, object: &LuaTable) -> Result<bool, LuaError> {
+    let Some(class) = object.metatable() else {
+        return Ok(false);
+    };
+
+    let builtins = lua.named_registry_value::<Option<LuaTable>>(BUILTIN_MODULES)?;
+    Ok(builtins.is_some_and(|builtins| builtins.contains_key(class).unwrap_or(false)))
+}
+
+/// Stands for a module object as `self` so its methods resolve and it can
+/// be called to run local commands, without altering the tables of the
+/// script: fields are read from the object then from its metatable, which
+/// is the class for objects created by `setmetatable({}, self)`, and are
+/// written to the object
+fn proxy(lua: &Lua, object: &LuaTable) -> Result<LuaTable, LuaError> {
+    let proxy = lua
+        .load(
+            r#"
+            local object = ...
+            local class = getmetatable(object)
+
+            return setmetatable({}, {
+                __index = function(_, key)
+                    local value = object[key]
+                    if value == nil and type(class) == "table" then
+                        value = rawget(class, key)
+                    end
+
+                    return value
+                end,
+                __newindex = object,
+                __call = function(_, ...)
+                    return run_locally(...)
+                end,
+            })
+            "#,
+        )
+        .set_name("module_proxy")
+        .call::<LuaTable>(object)?;
+
+    Ok(proxy)
+}
+
+impl FromLua for Become {
+    fn from_lua(value: Value, _: &Lua) -> mlua::Result<Self> {
+        match value {
+            Value::Nil => Ok(Become::Inherit),
+            Value::Boolean(false) => Ok(Become::Disabled),
+            Value::String(user) => Ok(Become::User(user.to_str()?.to_string())),
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: String::from("become"),
+                message: Some(String::from("expected a user name or false")),
+            }),
+        }
+    }
+}
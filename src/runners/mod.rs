@@ -4,10 +4,11 @@ pub use lua::LuaRunner;
 use std::collections::HashMap;
 
 use crate::Error;
-use crate::context::{Context, Host};
+use crate::context::Host;
+use crate::tasks::TaskGraph;
 
 pub trait Runner {
     fn init(&mut self) -> Result<(), Error>;
     fn get_hosts(&mut self) -> Result<HashMap<String, Host>, Error>;
-    fn run(&mut self, ctx: Context) -> Result<(), Error>;
+    fn describe(&mut self, host: &Host) -> Result<TaskGraph, Error>;
 }
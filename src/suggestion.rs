@@ -0,0 +1,43 @@
+/// Closest candidate to a mistyped value, if one is close enough to be what
+/// was meant
+pub fn closest<'a>(value: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    let max_distance = (value.chars().count() / 3).max(1);
+
+    candidates
+        .into_iter()
+        .map(|candidate| (distance(value, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+/// Formats the suggestion to be appended to an error message
+pub fn did_you_mean(suggestion: &Option<String>) -> String {
+    match suggestion {
+        Some(suggestion) => format!(", did you mean `{}`?", suggestion),
+        None => String::new(),
+    }
+}
+
+/// Levenshtein distance
+fn distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<char>>();
+    let mut previous = (0..=b.len()).collect::<Vec<usize>>();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+
+        for (j, b_char) in b.iter().enumerate() {
+            let cost = usize::from(a_char != *b_char);
+            current.push(
+                (previous[j] + cost)
+                    .min(previous[j + 1] + 1)
+                    .min(current[j] + 1),
+            );
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
+}
@@ -0,0 +1,410 @@
+use crate::Error;
+use crate::context::{Become, Callable, Condition, Context};
+use crate::interrupt;
+use crate::suggestion;
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::ops::RangeInclusive;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Stage of the deploy a task runs in, tasks are ordered by phase first and
+/// by declaration order within a phase
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    Setup,
+    #[default]
+    Build,
+    Switch,
+    Verify,
+    Finalize,
+    Failure,
+    Rollback,
+}
+
+impl FromStr for Phase {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "setup" => Ok(Phase::Setup),
+            "build" => Ok(Phase::Build),
+            "switch" => Ok(Phase::Switch),
+            "verify" => Ok(Phase::Verify),
+            "finalize" => Ok(Phase::Finalize),
+            "failure" => Ok(Phase::Failure),
+            "rollback" => Ok(Phase::Rollback),
+            phase => Err(format!("unknown phase `{}`", phase)),
+        }
+    }
+}
+
+impl Display for Phase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Phase::Setup => write!(f, "setup"),
+            Phase::Build => write!(f, "build"),
+            Phase::Switch => write!(f, "switch"),
+            Phase::Verify => write!(f, "verify"),
+            Phase::Finalize => write!(f, "finalize"),
+            Phase::Failure => write!(f, "failure"),
+            Phase::Rollback => write!(f, "rollback"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TaskOptions {
+    pub phase: Phase,
+    pub become_user: Become,
+    /// Labels of the hosts the task runs on, every host when empty
+    pub on: Vec<String>,
+    /// Runs on the first host reaching the task only
+    pub once: bool,
+    /// Skips the task when it does not hold
+    pub when: Option<Rc<dyn Condition>>,
+    /// Number of times a failing task is run again
+    pub retries: u32,
+    pub retry_delay: Duration,
+    /// Time after which the commands of the task are stopped
+    pub timeout: Option<Duration>,
+    /// Runs again when a failed deploy is resumed, for tasks undone by the
+    /// failure tasks such as taking the lock
+    pub rerun: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct Task {
+    pub name: String,
+    pub origin: String,
+    /// Declared by a module of the library rather than by the script
+    pub builtin: bool,
+    /// Key of the task it was placed after with `after()`, which it depends on
+    pub after: Option<String>,
+    pub callable: Rc<dyn Callable>,
+    pub options: TaskOptions,
+}
+
+impl Task {
+    /// Identifies the task across the task graphs of the hosts
+    pub fn key(&self) -> String {
+        format!("{}.{}", self.origin, self.name)
+    }
+
+    /// Tasks are named on the command line by their name, or by their key
+    /// when the name is shared by several modules
+    fn is_named(&self, name: &str) -> bool {
+        self.name == name || self.key() == name
+    }
+
+    fn skip_reason(&self, ctx: &Context) -> Result<Option<Skip>, Error> {
+        if !self.options.rerun
+            && let Some(progress) = &ctx.progress
+            && progress.has_completed(self)
+        {
+            return Ok(Some(Skip::Completed));
+        }
+
+        let on = &self.options.on;
+        if !on.is_empty() && !on.iter().any(|label| ctx.host.labels.contains(label)) {
+            return Ok(Some(Skip::Excluded(format!(
+                "host has none of the labels {}",
+                on.join(", ")
+            ))));
+        }
+
+        if let Some(reason) = ctx.filter.excludes(self) {
+            return Ok(Some(Skip::Excluded(String::from(reason))));
+        }
+
+        if self.options.once && ctx.run_once.has_run(self) {
+            return Ok(Some(Skip::RanElsewhere));
+        }
+
+        if let Some(when) = &self.options.when
+            && !when.test(ctx)?
+        {
+            return Ok(Some(Skip::Excluded(String::from(
+                "its `when` condition is false",
+            ))));
+        }
+
+        Ok(None)
+    }
+
+    /// Calls the task until it succeeds or runs out of retries
+    fn call(&self, ctx: &Context) -> Result<(), Error> {
+        let options = &self.options;
+        let mut attempt = 0;
+
+        loop {
+            let deadline = options.timeout.map(|timeout| Instant::now() + timeout);
+            ctx.deadline.set(deadline);
+            let result = self.callable.call(ctx);
+            ctx.deadline.set(None);
+
+            match result {
+                Err(err) if attempt < options.retries && !interrupt::requested() => {
+                    attempt += 1;
+
+                    let err = err.to_string();
+                    println!(
+                        "Retrying task {} ({}/{}) in {}s, it failed with: {}",
+                        self.name,
+                        attempt,
+                        options.retries,
+                        options.retry_delay.as_secs(),
+                        err.lines().next().unwrap_or_default(),
+                    );
+
+                    if ctx.plan.is_none() {
+                        interrupt::sleep(options.retry_delay)?;
+                    }
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// Tasks picked from the command line with `--skip` and `--only`
+#[derive(Clone, Debug, Default)]
+pub struct TaskFilter {
+    pub skip: Vec<String>,
+    pub only: Vec<String>,
+}
+
+impl TaskFilter {
+    /// Built-in tasks manage the release so `--only` keeps them, they can
+    /// still be skipped explicitly
+    fn excludes(&self, task: &Task) -> Option<&'static str> {
+        if self.skip.iter().any(|name| task.is_named(name)) {
+            return Some("excluded by --skip");
+        }
+
+        let selected = self.only.iter().any(|name| task.is_named(name));
+        if !self.only.is_empty() && !task.builtin && !selected {
+            return Some("not selected by --only");
+        }
+
+        None
+    }
+
+    /// Names that match no task are most likely typos
+    pub fn check(&self, graph: &TaskGraph) -> Result<(), Error> {
+        for name in self.skip.iter().chain(&self.only) {
+            graph.find(name)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Tasks that ran with the `once` option, shared by every host of a deploy
+#[derive(Debug, Default)]
+pub struct RunOnce(RefCell<BTreeSet<String>>);
+
+impl RunOnce {
+    fn has_run(&self, task: &Task) -> bool {
+        self.0.borrow().contains(&task.key())
+    }
+
+    fn mark(&self, task: &Task) {
+        self.0.borrow_mut().insert(task.key());
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct TaskGraph {
+    tasks: Vec<Task>,
+}
+
+impl TaskGraph {
+    pub fn push(&mut self, task: Task) {
+        self.tasks.push(task);
+    }
+
+    pub fn insert_after(&mut self, index: usize, task: Task) {
+        self.tasks.insert(index + 1, task);
+    }
+
+    pub fn remove(&mut self, index: usize) -> Task {
+        self.tasks.remove(index)
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Task> {
+        self.tasks.get(index)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Task> {
+        self.tasks.get_mut(index)
+    }
+
+    pub fn position(&self, predicate: impl Fn(&Task) -> bool) -> Option<usize> {
+        self.tasks.iter().position(predicate)
+    }
+
+    /// Task named on the command line, see [`Task::is_named`]
+    pub fn find(&self, name: &str) -> Result<&Task, Error> {
+        let matches = self
+            .tasks
+            .iter()
+            .filter(|task| task.is_named(name))
+            .collect::<Vec<&Task>>();
+
+        match matches.as_slice() {
+            [task] => Ok(task),
+            [] => Err(Error::UnknownTask {
+                name: name.to_string(),
+                suggestion: suggestion::closest(
+                    name,
+                    self.tasks.iter().map(|task| task.name.as_str()),
+                )
+                .map(str::to_string),
+            }),
+            tasks => Err(Error::AmbiguousTask {
+                name: name.to_string(),
+                tasks: tasks.iter().map(|task| task.key()).collect(),
+            }),
+        }
+    }
+
+    /// Chain of tasks a task was placed after with `after()`, the first one
+    /// to run comes first
+    pub fn dependencies(&self, task: &Task) -> Vec<&Task> {
+        let mut dependencies = Vec::<&Task>::new();
+        let mut current = task;
+
+        while let Some(anchor) = &current.after {
+            let Some(dependency) = self.tasks.iter().find(|task| task.key() == *anchor) else {
+                break;
+            };
+
+            if dependencies
+                .iter()
+                .any(|task| std::ptr::eq(*task, dependency))
+            {
+                //tasks placed after each other in a loop
+                break;
+            }
+
+            dependencies.push(dependency);
+            current = dependency;
+        }
+
+        dependencies.reverse();
+        dependencies
+    }
+
+    /// Runs the given tasks in order, without the failure tasks
+    pub fn run_selected(&self, ctx: &mut Context, tasks: &[&Task]) -> Result<(), Error> {
+        run_tasks(tasks, ctx)
+    }
+
+    /// Tasks in execution order, see [`Phase`]
+    pub fn ordered(&self) -> Vec<&Task> {
+        let mut tasks = self.tasks.iter().collect::<Vec<&Task>>();
+        tasks.sort_by_key(|task| task.options.phase);
+
+        tasks
+    }
+
+    /// Runs the tasks of the given phases in order
+    pub fn run_phases(
+        &self,
+        ctx: &mut Context,
+        phases: RangeInclusive<Phase>,
+    ) -> Result<(), Error> {
+        let tasks = self
+            .ordered()
+            .into_iter()
+            .filter(|task| phases.contains(&task.options.phase))
+            .collect::<Vec<&Task>>();
+
+        run_tasks(&tasks, ctx)
+    }
+
+    /// Runs the tasks of the failure phase, their own failure is only
+    /// reported so the error that caused it is the one returned
+    pub fn fail(&self, ctx: &mut Context) {
+        if let Err(failure_err) = self.run_phases(ctx, Phase::Failure..=Phase::Failure) {
+            eprintln!("Failure tasks did not complete: {}", failure_err);
+        }
+    }
+}
+
+/// Why a task does not run
+enum Skip {
+    /// Ran before the deploy was resumed
+    Completed,
+    /// A `once` task that ran on another host
+    RanElsewhere,
+    /// Left out by its options or the command line, a resumed deploy checks
+    /// them again
+    Excluded(String),
+}
+
+impl Skip {
+    /// Whether the resumed deploy skips the task too
+    fn is_done(&self) -> bool {
+        !matches!(self, Skip::Excluded(_))
+    }
+}
+
+impl Display for Skip {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Skip::Completed => write!(f, "completed before the deploy was resumed"),
+            Skip::RanElsewhere => write!(f, "already ran on another host"),
+            Skip::Excluded(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+fn run_tasks(tasks: &[&Task], ctx: &mut Context) -> Result<(), Error> {
+    for task in tasks {
+        interrupt::check()?;
+
+        let skip_reason = task
+            .skip_reason(ctx)
+            .map_err(|err| Error::Task(task.name.clone(), Box::new(err)))?;
+
+        let done = match skip_reason {
+            Some(reason) => {
+                println!("Skipping task {}, {}", task.name, reason);
+                reason.is_done()
+            }
+            None => {
+                println!("Running task {}", task.name);
+
+                ctx.task = task.options.clone();
+                let result = task.call(ctx);
+                ctx.task = TaskOptions::default();
+
+                result.map_err(|err| Error::Task(task.name.clone(), Box::new(err)))?;
+
+                if task.options.once {
+                    ctx.run_once.mark(task);
+                }
+
+                true
+            }
+        };
+
+        //the failure and rollback tasks run again if the resumed deploy fails
+        if done
+            && task.options.phase < Phase::Failure
+            && let Some(progress) = &mut ctx.progress
+        {
+            progress.complete(task);
+
+            if ctx.plan.is_none() {
+                progress.save()?;
+            }
+        }
+    }
+
+    Ok(())
+}
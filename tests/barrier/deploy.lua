@@ -0,0 +1,50 @@
+function setup()
+    default({
+        recipe = Recipe,
+        repository = "https://github.com/mbenoukaiss/ettac.git",
+        path = "/tmp/ettac",
+        hostname = "127.0.0.1",
+        port = 7122,
+        user = "bob",
+        private_key = env("PRIVATE_KEY"),
+    })
+
+    host("web1", { labels = { "web" } })
+    host("web2", { labels = { "web", "broken" } })
+    host("web3", { labels = { "web" } })
+    host("web4", { labels = { "web", "unhealthy" } })
+end
+
+Recipe = {}
+
+function Recipe:new()
+    return setmetatable({}, self)
+end
+
+local function has_label(name)
+    for _, label in ipairs(deploy.labels) do
+        if label == name then
+            return true
+        end
+    end
+
+    return false
+end
+
+function Recipe:build()
+    if has_label("broken") then
+        error("build failed")
+    end
+end
+
+function Recipe:smoke_test()
+    if has_label("unhealthy") then
+        error("smoke test failed")
+    end
+end
+
+function Recipe:describe()
+    use(System:new())
+    task(self.build)
+    task(self.smoke_test, { phase = "verify" })
+end
@@ -0,0 +1,154 @@
+use crate::BOB_PRIVATE_KEY;
+use assert_cmd::{Command, cargo_bin};
+use predicates::prelude::*;
+
+#[test]
+fn test_barrier_switches_together() {
+    let expected_output = predicate::str::is_match(
+        "(?s)Planning host web1.*Planning host web3.*Switching host web1.*Switching host web3.*Finalizing host web1",
+    )
+    .unwrap();
+
+    Command::new(cargo_bin!())
+        .current_dir("tests/barrier")
+        .args(["--dry-run", "web1", "web3"])
+        .env("PRIVATE_KEY", BOB_PRIVATE_KEY)
+        .assert()
+        .success()
+        .stdout(expected_output);
+}
+
+#[test]
+fn test_barrier_aborts_prepared_hosts() {
+    Command::new(cargo_bin!())
+        .current_dir("tests/barrier")
+        .args(["--dry-run", "web1", "web2", "web3"])
+        .env("PRIVATE_KEY", BOB_PRIVATE_KEY)
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("Aborting host web1"))
+        .stdout(predicate::str::contains("Aborting host web2"))
+        .stdout(predicate::str::contains("Preparing host web3").not())
+        .stdout(predicate::str::contains("Aborting host web3").not())
+        .stdout(predicate::str::contains("Switching host").not())
+        .stderr(predicate::str::starts_with(
+            "host `web2`: task `build` failed",
+        ));
+}
+
+#[test]
+fn test_canary_deploys_one_host_first() {
+    let expected_output = predicate::str::is_match(
+        "(?s)Deploying batch 1 of 2\nPlanning host web1\n.*Finalizing host web1\n.*Deploying batch 2 of 2\nPlanning host web3\n",
+    )
+    .unwrap();
+
+    Command::new(cargo_bin!())
+        .current_dir("tests/barrier")
+        .args(["--dry-run", "-o", "strategy=canary", "web1", "web3"])
+        .env("PRIVATE_KEY", BOB_PRIVATE_KEY)
+        .assert()
+        .success()
+        .stdout(expected_output);
+}
+
+#[test]
+fn test_rolling_rolls_back_switched_hosts() {
+    Command::new(cargo_bin!())
+        .current_dir("tests/barrier")
+        .args([
+            "--dry-run",
+            "-o",
+            "strategy=rolling",
+            "-o",
+            "batch_size=50%",
+            "-o",
+            "batch_pause=5",
+            "-o",
+            "rollback_on_failure=true",
+            "web1",
+            "web3",
+            "web2",
+        ])
+        .env("PRIVATE_KEY", BOB_PRIVATE_KEY)
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("Deploying batch 1 of 2"))
+        .stdout(predicate::str::contains(
+            "Pausing for 5s before the next batch",
+        ))
+        .stdout(predicate::str::contains("Rolling back host web1"))
+        .stdout(predicate::str::contains("Rolling back host web3"))
+        .stdout(predicate::str::contains("Aborting host web1").not())
+        .stdout(predicate::str::contains("Aborting host web2"));
+}
+
+#[test]
+fn test_auto_rollback_reverts_failing_host() {
+    let expected_output = predicate::str::is_match(
+        "(?s)Running task smoke_test\nRolling back host web4\nRunning task rollback\nAborting host web4\n",
+    )
+    .unwrap();
+
+    Command::new(cargo_bin!())
+        .current_dir("tests/barrier")
+        .args(["--dry-run", "-o", "auto_rollback=true", "web4"])
+        .env("PRIVATE_KEY", BOB_PRIVATE_KEY)
+        .assert()
+        .failure()
+        .stdout(expected_output)
+        .stderr(predicate::str::starts_with(
+            "host `web4`: task `smoke_test` failed",
+        ));
+}
+
+#[test]
+fn test_keep_going_deploys_other_hosts() {
+    Command::new(cargo_bin!())
+        .current_dir("tests/barrier")
+        .args(["--dry-run", "--keep-going", "web1", "web2", "web3"])
+        .env("PRIVATE_KEY", BOB_PRIVATE_KEY)
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("Finalizing host web3"))
+        .stdout(predicate::str::is_match("web1 +deployed").unwrap())
+        .stdout(predicate::str::is_match("web2 +failed +[0-9.]+s +build").unwrap())
+        .stdout(predicate::str::is_match("web3 +deployed").unwrap())
+        .stderr(predicate::str::ends_with(
+            "deploy failed on 1 host(s): web2\n",
+        ));
+}
+
+#[test]
+fn test_resume_without_failed_deploy() {
+    Command::new(cargo_bin!())
+        .current_dir("tests/barrier")
+        .args(["resume", "web1"])
+        .env("PRIVATE_KEY", BOB_PRIVATE_KEY)
+        .assert()
+        .failure()
+        .stderr("no failed deploy of host `web1` to resume\n");
+}
+
+#[test]
+fn test_resume_refuses_changed_script() {
+    let progress = "tests/barrier/.ettac/progress/web3.json";
+    std::fs::create_dir_all("tests/barrier/.ettac/progress").unwrap();
+    std::fs::write(
+        progress,
+        r#"{ "release": "20260101000000", "recipe": "0000000000000000", "completed": ["System.lock"] }"#,
+    )
+    .unwrap();
+
+    let assert = Command::new(cargo_bin!())
+        .current_dir("tests/barrier")
+        .args(["resume", "web3"])
+        .env("PRIVATE_KEY", BOB_PRIVATE_KEY)
+        .assert();
+
+    std::fs::remove_file(progress).unwrap();
+
+    assert.failure().stderr(
+        "the script changed since the deploy of host `web3` failed, start a new deploy instead\n",
+    );
+}
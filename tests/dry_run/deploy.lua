@@ -0,0 +1,30 @@
+function setup()
+    default({
+        recipe = Recipe,
+        repository = "https://github.com/mbenoukaiss/ettac.git",
+        path = "/tmp/ettac",
+    })
+
+    host("local", {})
+end
+
+local upper = string.upper
+
+Recipe = {}
+
+function Recipe:new()
+    return setmetatable({}, self)
+end
+
+function Recipe:status()
+    remote("echo discarded")
+    self("echo discarded locally")
+
+    local status = remote("systemctl is-active app")
+    print("Output is a " .. type(status) .. " of length " .. #status)
+    print("Strings untouched: " .. tostring(string.upper == upper))
+end
+
+function Recipe:describe()
+    task(self.status)
+end
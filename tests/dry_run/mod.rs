@@ -0,0 +1,18 @@
+use assert_cmd::{Command, cargo_bin};
+use predicates::prelude::*;
+
+#[test]
+fn test_dry_run_output() {
+    Command::new(cargo_bin!())
+        .current_dir("tests/dry_run")
+        .args(["--dry-run", "local"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("output of #1").not())
+        .stdout(predicate::str::contains("output of #2").not())
+        .stdout(predicate::str::contains(
+            "output of #3 is read by the script",
+        ))
+        .stdout(predicate::str::contains("Output is a string of length 0\n"))
+        .stdout(predicate::str::contains("Strings untouched: true\n"));
+}
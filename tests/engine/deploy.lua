@@ -0,0 +1,77 @@
+function setup()
+    default({
+        recipe = Recipe,
+        repository = env("REPOSITORY"),
+        path = env("DEPLOY_PATH"),
+        keep_releases = 2,
+        persistent_files = { ".env" },
+        persistent_dirs = { "storage" },
+    })
+
+    host("local", {})
+    host("hooks", { recipe = Hooks })
+end
+
+Recipe = {}
+
+function Recipe:new()
+    return setmetatable({}, self)
+end
+
+function Recipe:build()
+    remote("echo built > build.txt")
+end
+
+function Recipe:describe()
+    use(System:new())
+    task(self.build)
+end
+
+-- declares its tasks out of order and rearranges them with the describe
+-- functions
+Hooks = {}
+
+function Hooks:new()
+    return setmetatable({}, self)
+end
+
+function Hooks:announce()
+    print("Announced")
+end
+
+function Hooks:flaky()
+    error("flaky failed")
+end
+
+function Hooks:build()
+    print("Built")
+end
+
+function Hooks:removed()
+    print("Removed task ran")
+end
+
+function Hooks:notify()
+    print("Notified")
+end
+
+function Hooks:describe()
+    task(self.announce, { phase = "finalize" })
+    task(self.flaky)
+    task(self.build)
+    task(self.removed)
+
+    after(self.build, self.notify)
+    remove(self.removed)
+
+    wrap(self.build, function(inner)
+        print("Before build")
+        inner()
+        print("After build")
+    end)
+
+    catch(self.flaky, function(err)
+        print("Caught " .. err)
+        continue()
+    end)
+end
@@ -0,0 +1,134 @@
+use assert_cmd::assert::Assert;
+use assert_cmd::{Command, cargo_bin};
+use predicates::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+fn temp_path(test: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("ettac-engine-{}-{}", test, std::process::id()))
+}
+
+fn deploy(path: &Path) -> Assert {
+    deploy_with(path, &["local"])
+}
+
+fn deploy_with(path: &Path, args: &[&str]) -> Assert {
+    Command::new(cargo_bin!())
+        .current_dir("tests/engine")
+        .args(args)
+        .env("REPOSITORY", env!("CARGO_MANIFEST_DIR"))
+        .env("DEPLOY_PATH", path)
+        .assert()
+}
+
+#[test]
+fn test_deploy_lock_held() {
+    let path = temp_path("lock");
+    fs::create_dir_all(path.join(".ettac.lock")).unwrap();
+
+    deploy(&path)
+        .failure()
+        .stdout(predicate::str::contains("Running task build").not())
+        .stderr(predicate::str::contains(format!(
+            "another deploy is running, remove {}/.ettac.lock if it is not the case",
+            path.display()
+        )));
+
+    //the lock belongs to the other deploy
+    assert!(path.join(".ettac.lock").is_dir());
+    assert!(!path.join("current").exists());
+
+    fs::remove_dir_all(&path).unwrap();
+}
+
+fn releases(path: &Path) -> Vec<String> {
+    let mut releases = fs::read_dir(path.join("releases"))
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect::<Vec<_>>();
+
+    releases.sort();
+    releases
+}
+
+#[test]
+fn test_deploy_release_layout() {
+    let path = temp_path("layout");
+
+    let expected_output = predicate::str::is_match(
+        "(?s)Preparing host local\nRunning task lock.*Running task prepare.*Running task checkout.*Running task link_persistent.*Running task build.*Switching host local\nRunning task switch.*Finalizing host local\nRunning task cleanup.*Running task unlock",
+    )
+    .unwrap();
+
+    deploy(&path).success().stdout(expected_output);
+
+    let releases = releases(&path);
+    assert_eq!(releases.len(), 1);
+    assert_eq!(releases[0].len(), 14);
+    assert!(releases[0].chars().all(|c| c.is_ascii_digit()));
+
+    let current = path.join("current");
+    assert_eq!(
+        fs::read_link(&current).unwrap(),
+        Path::new("releases").join(&releases[0])
+    );
+    assert_eq!(
+        fs::read_to_string(current.join("build.txt")).unwrap(),
+        "built\n"
+    );
+
+    //persistent paths point to the shared directory
+    assert_eq!(
+        fs::read_link(current.join(".env")).unwrap(),
+        path.join("shared/.env")
+    );
+    assert_eq!(
+        fs::read_link(current.join("storage")).unwrap(),
+        path.join("shared/storage")
+    );
+    assert!(path.join("shared/storage").is_dir());
+    assert!(!path.join(".ettac.lock").exists());
+
+    fs::remove_dir_all(&path).unwrap();
+}
+
+#[test]
+fn test_deploy_cleans_up_releases() {
+    let path = temp_path("cleanup");
+
+    let mut deployed = Vec::new();
+    for _ in 0..3 {
+        deploy_with(&path, &["-o", "keep_releases=1", "local"]).success();
+        deployed.push(fs::read_link(path.join("current")).unwrap());
+
+        //release names have a resolution of a second
+        thread::sleep(Duration::from_millis(1100));
+    }
+
+    //the live release and the one a rollback would switch back to
+    let kept = deployed[1..]
+        .iter()
+        .map(|release| release.file_name().unwrap().to_str().unwrap().to_owned())
+        .collect::<Vec<_>>();
+
+    assert_eq!(releases(&path), kept);
+
+    fs::remove_dir_all(&path).unwrap();
+}
+
+#[test]
+fn test_describe_functions() {
+    let path = temp_path("hooks");
+
+    let expected_output = predicate::str::is_match(
+        "(?s)Running task flaky\nCaught .*flaky failed.*Running task build\nBefore build\nBuilt\nAfter build\nRunning task notify\nNotified\nSwitching host hooks\nFinalizing host hooks\nRunning task announce\nAnnounced\n",
+    )
+    .unwrap();
+
+    deploy_with(&path, &["hooks"])
+        .success()
+        .stdout(expected_output)
+        .stdout(predicate::str::contains("Removed task ran").not());
+}
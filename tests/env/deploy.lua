@@ -0,0 +1,32 @@
+dofile("../helpers.lua")
+
+function setup()
+    default({
+        recipe = Recipe,
+        repository = "https://github.com/mbenoukaiss/ettac.git",
+        path = "/tmp/ettac",
+        env = { APP_ENV = "prod", GREETING = "it's \"quoted\" $HOME" },
+    })
+
+    host("local", { env = { APP_DEBUG = "0" } })
+    host("invalid", { env = { ["APP-ENV"] = "prod" } })
+end
+
+Recipe = {}
+
+function Recipe:new()
+    return setmetatable({}, self)
+end
+
+function Recipe:variables()
+    remote("true", { cwd = "/" })
+
+    try("host", "echo $APP_ENV $APP_DEBUG; echo \"$GREETING\"", { cwd = "/" })
+    try("command", "echo $APP_ENV $EXTRA", { cwd = "/", env = { APP_ENV = "test", EXTRA = "a b" } })
+    try("argv", { "printenv", "APP_ENV" }, { cwd = "/", env = { APP_ENV = "argv" } })
+    try("invalid name", "true", { cwd = "/", env = { ["1ST"] = "x" } })
+end
+
+function Recipe:describe()
+    task(self.variables)
+end
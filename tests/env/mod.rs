@@ -0,0 +1,31 @@
+use assert_cmd::{Command, cargo_bin};
+use predicates::prelude::*;
+
+#[test]
+fn test_env() {
+    Command::new(cargo_bin!())
+        .current_dir("tests/env")
+        .arg("local")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "host: prod 0\nit's \"quoted\" $HOME\n",
+        ))
+        .stdout(predicate::str::contains("command: test a b\n"))
+        .stdout(predicate::str::contains("argv: argv\n"))
+        .stdout(predicate::str::contains(
+            "invalid name failed: `1ST` is not a valid environment variable name\n",
+        ));
+}
+
+#[test]
+fn test_invalid_host_env() {
+    Command::new(cargo_bin!())
+        .current_dir("tests/env")
+        .arg("invalid")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "host `invalid`: task `variables` failed: script runtime error : `APP-ENV` is not a valid environment variable name",
+        ));
+}
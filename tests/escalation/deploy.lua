@@ -0,0 +1,28 @@
+function setup()
+    default({
+        recipe = Recipe,
+        repository = "https://github.com/mbenoukaiss/ettac.git",
+        path = "/tmp",
+        become = "deployer",
+    })
+
+    host("local", { become_password = "s3cret" })
+    host("wrong-password", { become_password = "nope" })
+    host("no-password", {})
+    host("su", { become_method = "su", become_password = "s3cret" })
+end
+
+Recipe = {}
+
+function Recipe:new()
+    return setmetatable({}, self)
+end
+
+function Recipe:whoami()
+    print("Running as " .. remote("echo $BECOME_USER", { cwd = "/" }))
+    print("Running as " .. remote("echo ${BECOME_USER:-myself}", { cwd = "/", become = false }))
+end
+
+function Recipe:describe()
+    task(self.whoami)
+end
@@ -0,0 +1,100 @@
+use assert_cmd::assert::Assert;
+use assert_cmd::{Command, cargo_bin};
+use predicates::prelude::*;
+use std::env;
+use std::os::unix::process::CommandExt;
+
+/// Deploys with the `sudo` of the fixture, which accepts the password `s3cret`
+fn deploy(host: &str) -> Assert {
+    let path = format!(
+        "{}/tests/escalation/bin:{}",
+        env!("CARGO_MANIFEST_DIR"),
+        env::var("PATH").unwrap_or_default()
+    );
+
+    let mut command = std::process::Command::new(cargo_bin!());
+    command
+        .current_dir("tests/escalation")
+        .arg(host)
+        .env("PATH", path);
+
+    //a new session has no terminal to prompt for the password on
+    unsafe {
+        command.pre_exec(|| {
+            libc::setsid();
+            Ok(())
+        });
+    }
+
+    Command::from_std(command).assert()
+}
+
+fn failure(host: &str, error: &str) {
+    deploy(host)
+        .failure()
+        .stderr(predicate::str::contains(format!(
+            "host `{}`: task `whoami` failed: script runtime error : access error: {}",
+            host, error
+        )));
+}
+
+#[test]
+fn test_become_with_password() {
+    deploy("local")
+        .success()
+        .stdout(predicate::str::contains("Running as deployer\n"))
+        .stdout(predicate::str::contains("Running as myself\n"))
+        .stdout(predicate::str::contains("__ettac_become").not());
+}
+
+#[test]
+fn test_become_wrong_password() {
+    failure(
+        "wrong-password",
+        "The password to become another user was rejected",
+    );
+}
+
+#[test]
+fn test_become_without_password() {
+    failure(
+        "no-password",
+        "A password is required to become another user",
+    );
+}
+
+#[test]
+fn test_become_su_on_local_host() {
+    failure(
+        "su",
+        "`su` cannot be used on local hosts as it only reads the password from a terminal, use `sudo` instead",
+    );
+}
+
+#[test]
+fn test_become_override() {
+    Command::new(cargo_bin!())
+        .current_dir("tests/escalation")
+        .args(["-o", "become=false", "hosts", "--json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"become\": null"))
+        .stdout(predicate::str::contains("\"become\": \"deployer\"").not());
+
+    Command::new(cargo_bin!())
+        .current_dir("tests/escalation")
+        .args(["-o", "become=", "local"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Running as \n"))
+        .stdout(predicate::str::contains("Running as myself\n"));
+
+    Command::new(cargo_bin!())
+        .current_dir("tests/escalation")
+        .args(["-o", "become=www data", "hosts"])
+        .assert()
+        .failure()
+        .stderr(
+            "invalid deploy config in setup(): invalid override `become`: expected a user name, or `false` or an empty value to disable become\n",
+        );
+}
@@ -0,0 +1,20 @@
+function setup()
+    default({
+        recipe = Recipe,
+        repository = "https://github.com/mbenoukaiss/ettac.git",
+        path = "/tmp/ettac",
+    })
+
+    host("web-1", { extends = "web-2" })
+    host("web-2", { extends = "web-1" })
+end
+
+Recipe = {}
+
+function Recipe:new()
+    return setmetatable({}, self)
+end
+
+function Recipe:describe()
+    use(System:new())
+end
@@ -0,0 +1,18 @@
+use assert_cmd::{Command, cargo_bin};
+use predicates::prelude::*;
+
+#[test]
+fn test_extends_cycle() {
+    let expected_error =
+        predicate::str::contains("hosts extend each other: web-1 -> web-2 -> web-1").or(
+            predicate::str::contains("hosts extend each other: web-2 -> web-1 -> web-2"),
+        );
+
+    Command::new(cargo_bin!())
+        .current_dir("tests/extends_cycle")
+        .arg("web-1")
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(expected_error);
+}
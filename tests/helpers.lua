@@ -0,0 +1,13 @@
+-- Helpers shared by the fixtures, loaded with `dofile("../helpers.lua")` as
+-- the tests run from the directory of their fixture
+
+-- runs a command and prints its output, or the first line of the error so a
+-- failing command does not stop the task
+function try(name, cmd, options)
+    local ok, result = pcall(remote, cmd, options)
+    if ok then
+        print(name .. ": " .. result)
+    else
+        print(name .. " failed: " .. tostring(result):match("^[^\n]*"))
+    end
+end
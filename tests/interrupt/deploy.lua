@@ -0,0 +1,55 @@
+function setup()
+    default({
+        recipe = Recipe,
+        repository = "https://github.com/mbenoukaiss/ettac.git",
+        path = "/tmp/ettac-interrupt",
+    })
+
+    host("local", {})
+    host("kept", {})
+    host("stuck", { vars = { stuck_cleanup = true } })
+
+    -- nothing listens on the port so every connection attempt fails
+    host("unreachable", {
+        hostname = "127.0.0.1",
+        port = 1,
+        user = "ettac",
+        password = "ettac",
+        connect_retries = 1,
+        connect_timeout = 1,
+    })
+end
+
+Recipe = {}
+
+function Recipe:new()
+    return setmetatable({}, self)
+end
+
+function Recipe:prepare()
+    remote("mkdir -p " .. shell_quote(deploy.path), { cwd = "/" })
+end
+
+function Recipe:build()
+    remote("sleep 30", { cwd = deploy.path })
+end
+
+function Recipe:package()
+    remote("echo packaged", { cwd = deploy.path })
+end
+
+function Recipe:cleanup()
+    print("Discarding release: " .. tostring(deploy.discard_release))
+
+    -- stays until the deploy is interrupted again
+    if deploy.vars.stuck_cleanup then
+        remote("echo $$ > stuck.pid; exec sleep 30", { cwd = deploy.path })
+    end
+end
+
+function Recipe:describe()
+    task(self.prepare, { phase = "setup" })
+    task(self.build)
+    task(self.package)
+    task(self.cleanup, { phase = "failure" })
+end
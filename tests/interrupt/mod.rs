@@ -0,0 +1,164 @@
+use assert_cmd::{Command as AssertCommand, cargo_bin};
+use predicates::prelude::*;
+use std::io::{BufRead, BufReader, Read};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Interrupts the deploy once `marker` is printed, on stderr when `on_stderr`
+/// is set and on stdout otherwise
+fn interrupted_deploy(args: &[&str], marker: &str, on_stderr: bool) -> (String, String) {
+    let started_at = Instant::now();
+    let mut child = Command::new(cargo_bin!())
+        .current_dir("tests/interrupt")
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut stderr = BufReader::new(child.stderr.take().unwrap());
+    let (mut stdout_output, mut stderr_output) = (String::new(), String::new());
+
+    let (watched, output): (&mut dyn BufRead, _) = match on_stderr {
+        true => (&mut stderr, &mut stderr_output),
+        false => (&mut stdout, &mut stdout_output),
+    };
+
+    while !output.contains(marker) {
+        assert_ne!(watched.read_line(output).unwrap(), 0, "{}", output);
+    }
+
+    thread::sleep(Duration::from_millis(200));
+    Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+
+    stdout.read_to_string(&mut stdout_output).unwrap();
+    stderr.read_to_string(&mut stderr_output).unwrap();
+
+    assert!(!child.wait().unwrap().success());
+    assert!(started_at.elapsed() < Duration::from_secs(20));
+
+    (stdout_output, stderr_output)
+}
+
+#[test]
+fn test_interrupt_stops_the_deploy() {
+    let (stdout, stderr) = interrupted_deploy(&["local"], "sleep 30", false);
+
+    assert!(stdout.contains("Aborting host local"));
+    assert!(stdout.contains("Discarding release: true"));
+    assert!(!stdout.contains("Running task package"));
+    assert!(stderr.contains("Interrupted, stopping the deploy"));
+    assert!(stderr.contains("the deploy was interrupted"));
+}
+
+#[test]
+fn test_interrupt_keeps_release() {
+    let (stdout, _) = interrupted_deploy(&["--keep-release", "kept"], "sleep 30", false);
+
+    assert!(stdout.contains("Discarding release: false"));
+    assert!(stdout.contains("ettac resume <host>"));
+
+    //the deploy can be resumed
+    std::fs::remove_file("tests/interrupt/.ettac/progress/kept.json").unwrap();
+}
+
+#[test]
+fn test_connection_retries() {
+    AssertCommand::new(cargo_bin!())
+        .current_dir("tests/interrupt")
+        .arg("unreachable")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "Connection to 127.0.0.1 failed (attempt 1/2)",
+        ))
+        .stderr(predicate::str::contains(
+            "could not connect to `127.0.0.1` after 2 attempt(s)",
+        ));
+}
+
+#[test]
+fn test_interrupt_stops_connection_retries() {
+    let started_at = Instant::now();
+    let args = ["-o", "connect_retries=5", "unreachable"];
+    let (stdout, stderr) = interrupted_deploy(&args, "retrying in 1s", true);
+
+    //the backoff grows to 16s over the 5 retries
+    assert!(started_at.elapsed() < Duration::from_secs(5));
+    assert!(stdout.contains("unreachable"));
+    assert!(stderr.contains("the deploy was interrupted"));
+}
+
+/// Whether a process ended, it is left as a zombie until its new parent
+/// reaps it
+fn has_ended(pid: &str) -> bool {
+    match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+        Ok(stat) => stat
+            .rsplit(") ")
+            .next()
+            .is_some_and(|state| state.starts_with('Z')),
+        Err(_) => true,
+    }
+}
+
+#[test]
+fn test_second_interrupt_kills_commands() {
+    let pid_file = "/tmp/ettac-interrupt/stuck.pid";
+    let _ = std::fs::remove_file(pid_file);
+
+    let mut child = Command::new(cargo_bin!())
+        .current_dir("tests/interrupt")
+        .arg("stuck")
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+    let mut output = String::new();
+    while !output.contains("sleep 30") {
+        assert_ne!(stdout.read_line(&mut output).unwrap(), 0, "{}", output);
+    }
+
+    thread::sleep(Duration::from_millis(200));
+    Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+
+    //the failure task runs a command until the deploy is interrupted again
+    let started_at = Instant::now();
+    let pid = loop {
+        if let Ok(pid) = std::fs::read_to_string(pid_file)
+            && !pid.trim().is_empty()
+        {
+            break pid.trim().to_string();
+        }
+
+        assert!(started_at.elapsed() < Duration::from_secs(10));
+        thread::sleep(Duration::from_millis(50));
+    };
+
+    Command::new("kill")
+        .args(["-INT", &child.id().to_string()])
+        .status()
+        .unwrap();
+
+    assert_eq!(child.wait().unwrap().code(), Some(130));
+
+    let started_at = Instant::now();
+    while !has_ended(&pid) {
+        assert!(
+            started_at.elapsed() < Duration::from_secs(5),
+            "command still runs"
+        );
+        thread::sleep(Duration::from_millis(50));
+    }
+
+    std::fs::remove_file(pid_file).unwrap();
+}
@@ -0,0 +1,19 @@
+function setup()
+    default({
+        recipe = Recipe,
+        repository = "https://github.com/mbenoukaiss/ettac.git",
+    })
+
+    inventory_json("hosts.json")
+    inventory_command("cat worker.toml", "toml")
+end
+
+Recipe = {}
+
+function Recipe:new()
+    return setmetatable({}, self)
+end
+
+function Recipe:describe()
+    use(System:new())
+end
@@ -0,0 +1,35 @@
+use assert_cmd::{Command, cargo_bin};
+use predicates::prelude::*;
+
+#[test]
+fn test_inventory() {
+    Command::new(cargo_bin!())
+        .current_dir("tests/inventory")
+        .arg("hosts")
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"web-1 +- +local +/tmp/ettac +web +0").unwrap())
+        .stdout(predicate::str::is_match(r"web-2 +- +local +/tmp/ettac +web +4").unwrap())
+        .stdout(predicate::str::is_match(r"worker +- +local +/tmp/worker").unwrap());
+}
+
+#[test]
+fn test_inventory_without_commands() {
+    Command::new(cargo_bin!())
+        .current_dir("tests/inventory")
+        .args(["--no-inventory-commands", "hosts"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"web-1 +- +local +/tmp/ettac +web +0").unwrap())
+        .stdout(predicate::str::contains("worker").not());
+}
+
+#[test]
+fn test_inventory_relative_to_the_script() {
+    Command::new(cargo_bin!())
+        .args(["-s", "tests/inventory/deploy.lua", "hosts"])
+        .assert()
+        .success()
+        .stdout(predicate::str::is_match(r"web-1 +- +local +/tmp/ettac +web +0").unwrap())
+        .stdout(predicate::str::is_match(r"worker +- +local +/tmp/worker").unwrap());
+}
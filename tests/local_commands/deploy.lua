@@ -0,0 +1,40 @@
+dofile("../helpers.lua")
+
+function setup()
+    default({
+        recipe = Recipe,
+        repository = "https://github.com/mbenoukaiss/ettac.git",
+        path = "/tmp/ettac",
+    })
+
+    host("local", {})
+    host("failing", { vars = { fail = "yes" } })
+end
+
+Recipe = {}
+
+function Recipe:new()
+    return setmetatable({}, self)
+end
+
+function Recipe:commands()
+    local args = { "printf", "%s|", "two words", "$HOME", "it's" }
+
+    try("piped", "printf 'b\\na\\n' | sort | tr '\\n' ' '", { cwd = "/" })
+    try("chained", "false || echo fallback && echo chained", { cwd = "/" })
+    try("redirected", "echo redirected > /dev/null; echo done 2>&1", { cwd = "/" })
+    try("argv", args, { cwd = "/" })
+    try("joined", shell_join(args), { cwd = "/" })
+    try("split", "printf '%s|' 'two words' three", { cwd = "/", shell = false })
+    try("status", "echo oops >&2; exit 3", { cwd = "/" })
+    try("empty", "  ", { cwd = "/" })
+    try("empty argv", {}, { cwd = "/" })
+
+    if deploy.vars.fail then
+        remote("exit 4", { cwd = "/" })
+    end
+end
+
+function Recipe:describe()
+    task(self.commands)
+end
@@ -0,0 +1,37 @@
+use assert_cmd::{Command, cargo_bin};
+use predicates::prelude::*;
+
+#[test]
+fn test_local_commands() {
+    Command::new(cargo_bin!())
+        .current_dir("tests/local_commands")
+        .arg("local")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("piped: a b\n"))
+        .stdout(predicate::str::contains("chained: fallback\nchained\n"))
+        .stdout(predicate::str::contains("redirected: done\n"))
+        .stdout(predicate::str::contains("argv: two words|$HOME|it's|\n"))
+        .stdout(predicate::str::contains("joined: two words|$HOME|it's|\n"))
+        .stdout(predicate::str::contains("split: two words|three|\n"))
+        .stdout(predicate::str::contains(
+            "status failed: command `echo oops >&2; exit 3` exited with status 3: oops\n",
+        ))
+        .stdout(predicate::str::contains("empty failed: command is empty\n"))
+        .stdout(predicate::str::contains(
+            "empty argv failed: command is empty\n",
+        ));
+}
+
+#[test]
+fn test_local_command_failure() {
+    Command::new(cargo_bin!())
+        .current_dir("tests/local_commands")
+        .arg("failing")
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("failing  failed"))
+        .stderr(predicate::str::contains(
+            "host `failing`: task `commands` failed: script runtime error : command `exit 4` exited with status 4",
+        ));
+}
@@ -0,0 +1,34 @@
+function setup()
+    default({
+        recipe = Recipe,
+        repository = "https://github.com/mbenoukaiss/ettac.git",
+        path = "/tmp/ettac",
+        hostname = "127.0.0.1",
+        port = 7122,
+        user = "bob",
+        private_key = env("PRIVATE_KEY"),
+    })
+
+    host("remote", {})
+end
+
+Recipe = {}
+
+function Recipe:new()
+    return setmetatable({}, self)
+end
+
+local function count_lines(output)
+    return select(2, string.gsub(output, "\n", "")) + 1
+end
+
+-- a few megabytes on a single stream, the other one staying quiet
+function Recipe:stream()
+    print("Read " .. count_lines(remote("seq 1 500000", { cwd = "/" })) .. " lines from stdout")
+    print("Read " .. remote("seq 1 500000 >&2; echo done", { cwd = "/" }) .. " after stderr")
+    print("Read " .. remote("printf '%s' __ettac_started__; echo done", { cwd = "/" }) .. " with the marker")
+end
+
+function Recipe:describe()
+    task(self.stream)
+end
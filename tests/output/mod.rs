@@ -0,0 +1,22 @@
+use crate::BOB_PRIVATE_KEY;
+use assert_cmd::{Command, cargo_bin};
+use predicates::prelude::*;
+use std::time::Duration;
+
+#[test]
+#[cfg_attr(not(feature = "integration"), ignore)]
+fn test_output_of_a_single_stream() {
+    //reading the quiet stream used to block for each buffer of the other one
+    Command::new(cargo_bin!())
+        .current_dir("tests/output")
+        .arg("remote")
+        .env("PRIVATE_KEY", BOB_PRIVATE_KEY)
+        .timeout(Duration::from_secs(30))
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Read 500000 lines from stdout"))
+        .stdout(predicate::str::contains("Read done after stderr"))
+        .stdout(predicate::str::contains(
+            "Read __ettac_started__done with the marker",
+        ));
+}
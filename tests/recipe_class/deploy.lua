@@ -0,0 +1,31 @@
+function setup()
+    default({
+        recipe = Recipe,
+        repository = "https://github.com/mbenoukaiss/ettac.git",
+        path = "/tmp",
+    })
+
+    host("local", {})
+    host("constructor", { recipe = Recipe.new })
+end
+
+Recipe = {}
+
+function Recipe:new()
+    return setmetatable({ runs = 0 }, self)
+end
+
+function Recipe:count()
+    self.runs = self.runs + 1
+    print("Ran " .. self("echo locally", { cwd = "/" }))
+end
+
+function Recipe:report()
+    print("Counted " .. self.runs .. " run(s)")
+    print("Class untouched: " .. tostring(rawget(Recipe, "__index") == nil and rawget(Recipe, "__call") == nil))
+end
+
+function Recipe:describe()
+    task(self.count)
+    task(self.report)
+end
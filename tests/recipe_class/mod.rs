@@ -0,0 +1,41 @@
+use assert_cmd::{Command, cargo_bin};
+use predicates::prelude::*;
+
+#[test]
+fn test_recipe_class() {
+    Command::new(cargo_bin!())
+        .current_dir("tests/recipe_class")
+        .arg("local")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Ran locally\n"))
+        .stdout(predicate::str::contains("Counted 1 run(s)\n"))
+        .stdout(predicate::str::contains("Class untouched: true\n"));
+}
+
+#[test]
+fn test_recipe_constructor_without_class() {
+    Command::new(cargo_bin!())
+        .current_dir("tests/recipe_class")
+        .arg("constructor")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains(
+            "recipe has no `describe` method, classes whose `new()` expects `self` are given as `recipe = Class`",
+        ));
+}
+
+#[test]
+fn test_describe_failure_keeps_going() {
+    Command::new(cargo_bin!())
+        .current_dir("tests/recipe_class")
+        .args(["--keep-going", "constructor", "local"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("Ran locally\n"))
+        .stdout(predicate::str::is_match("constructor +failed").unwrap())
+        .stdout(predicate::str::is_match("local +deployed").unwrap())
+        .stderr(predicate::str::contains(
+            "host `constructor`: script runtime error : runtime error: recipe has no `describe` method",
+        ));
+}
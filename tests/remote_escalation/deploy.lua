@@ -0,0 +1,31 @@
+function setup()
+    default({
+        recipe = Recipe,
+        repository = "https://github.com/mbenoukaiss/ettac.git",
+        path = "/var/www/ettac",
+        hostname = "127.0.0.1",
+        port = 7022,
+        user = "alice",
+        password = env("PASSWORD"),
+        become = "deployer",
+    })
+
+    host("sudo", { become_password = env("PASSWORD") })
+    host("su", { become_method = "su", become_password = env("BECOME_PASSWORD") })
+    host("wrong-password", { become_password = "nope" })
+end
+
+Recipe = {}
+
+function Recipe:new()
+    return setmetatable({}, self)
+end
+
+function Recipe:whoami()
+    print("Running as " .. remote("whoami", { cwd = "/" }))
+    print("Running as " .. remote("whoami", { cwd = "/", become = false }))
+end
+
+function Recipe:describe()
+    task(self.whoami)
+end
@@ -0,0 +1,44 @@
+use assert_cmd::assert::Assert;
+use assert_cmd::{Command, cargo_bin};
+use predicates::prelude::*;
+
+const PASSWORD: &str = "y0zHDHwv3X31";
+const BECOME_PASSWORD: &str = "Qm4vXk2tL9pw";
+
+/// Deploys on the container where `alice` may become `deployer`
+fn deploy(host: &str) -> Assert {
+    Command::new(cargo_bin!())
+        .current_dir("tests/remote_escalation")
+        .arg(host)
+        .env("PASSWORD", PASSWORD)
+        .env("BECOME_PASSWORD", BECOME_PASSWORD)
+        .assert()
+}
+
+#[test]
+#[cfg_attr(not(feature = "integration"), ignore)]
+fn test_become_with_sudo() {
+    deploy("sudo")
+        .success()
+        .stdout(predicate::str::contains("Running as deployer\n"))
+        .stdout(predicate::str::contains("Running as alice\n"));
+}
+
+#[test]
+#[cfg_attr(not(feature = "integration"), ignore)]
+fn test_become_with_su() {
+    deploy("su")
+        .success()
+        .stdout(predicate::str::contains("Running as deployer\n"))
+        .stdout(predicate::str::contains("Running as alice\n"));
+}
+
+#[test]
+#[cfg_attr(not(feature = "integration"), ignore)]
+fn test_become_wrong_password() {
+    deploy("wrong-password")
+        .failure()
+        .stderr(predicate::str::contains(
+            "host `wrong-password`: task `whoami` failed: script runtime error : access error: The password to become another user was rejected",
+        ));
+}
@@ -0,0 +1,29 @@
+function setup()
+    default({
+        recipe = Recipe,
+        repository = "https://github.com/mbenoukaiss/ettac.git",
+        path = "/tmp/ettac",
+        vars = { app = "shop" },
+    })
+
+    host("local", {})
+end
+
+Recipe = {}
+
+function Recipe:new()
+    return setmetatable({}, self)
+end
+
+function Recipe:inspect()
+    remote("docker inspect --format '{{.State.Status}}' {{app}}")
+end
+
+function Recipe:template()
+    remote("echo '{{{{ app }}' > {{ app }}.j2")
+end
+
+function Recipe:describe()
+    task(self.inspect)
+    task(self.template)
+end
@@ -0,0 +1,15 @@
+use assert_cmd::{Command, cargo_bin};
+use predicates::prelude::*;
+
+#[test]
+fn test_render_placeholders() {
+    Command::new(cargo_bin!())
+        .current_dir("tests/render")
+        .args(["--dry-run", "local"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "docker inspect --format '{{.State.Status}}' shop",
+        ))
+        .stdout(predicate::str::contains("echo '{{ app }}' > shop.j2"));
+}
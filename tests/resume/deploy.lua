@@ -0,0 +1,44 @@
+function setup()
+    default({
+        recipe = Recipe,
+        repository = env("REPOSITORY"),
+        path = env("DEPLOY_PATH"),
+        vars = { branch = "release" },
+    })
+
+    host("local", {})
+    host("team/web", {})
+end
+
+Recipe = {}
+
+function Recipe:new()
+    return setmetatable({}, self)
+end
+
+-- deploys a branch other than the default one of the repository
+function Recipe:checkout()
+    remote("git clone --branch {{branch}} " .. shell_quote(deploy.repository) .. " " .. shell_quote("releases/" .. deploy.release), { cwd = deploy.path })
+end
+
+function Recipe:build()
+    if env("FAIL_BUILD") then
+        error("build failed")
+    end
+
+    remote("touch built")
+end
+
+function Recipe:notes()
+    print("Wrote release notes")
+end
+
+function Recipe:describe()
+    local system = System:new()
+
+    use(system)
+    remove(system.checkout)
+    after(system.prepare, self.checkout)
+    task(self.notes)
+    task(self.build)
+end
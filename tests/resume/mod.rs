@@ -0,0 +1,118 @@
+use assert_cmd::assert::Assert;
+use assert_cmd::{Command, cargo_bin};
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+fn git(repository: &Path, args: &[&str]) {
+    let status = std::process::Command::new("git")
+        .current_dir(repository)
+        .args(["-c", "user.name=ettac", "-c", "user.email=ettac@localhost"])
+        .args(args)
+        .status()
+        .unwrap();
+
+    assert!(status.success());
+}
+
+fn ettac(dir: &Path, args: &[&str], fail: bool) -> Assert {
+    let mut command = Command::new(cargo_bin!());
+    command
+        .current_dir("tests/resume")
+        .args(args)
+        .env("REPOSITORY", dir.join("repository"))
+        .env("DEPLOY_PATH", dir.join("deploy"));
+
+    if fail {
+        command.env("FAIL_BUILD", "1");
+    }
+
+    command.assert()
+}
+
+/// Repository whose default branch is not the deployed one
+fn create_repository(repository: &Path) {
+    fs::create_dir_all(repository).unwrap();
+
+    git(repository, &["init", "--quiet", "--initial-branch", "main"]);
+    git(
+        repository,
+        &["commit", "--quiet", "--allow-empty", "-m", "main"],
+    );
+    git(repository, &["checkout", "--quiet", "-b", "release"]);
+    git(
+        repository,
+        &["commit", "--quiet", "--allow-empty", "-m", "release"],
+    );
+    git(repository, &["checkout", "--quiet", "main"]);
+}
+
+#[test]
+fn test_resume_failed_deploy() {
+    let dir = std::env::temp_dir().join(format!("ettac-resume-{}", std::process::id()));
+    let repository = dir.join("repository");
+    create_repository(&repository);
+
+    ettac(&dir, &["local"], true)
+        .failure()
+        .stdout(predicate::str::contains("ettac resume <host>"));
+
+    ettac(&dir, &["resume", "local"], false)
+        .success()
+        .stdout(predicate::str::contains(
+            "Skipping task checkout, completed before the deploy was resumed",
+        ))
+        .stdout(predicate::str::contains("Running task build"));
+
+    let current = dir
+        .join("deploy")
+        .join(fs::read_link(dir.join("deploy/current")).unwrap());
+    assert!(current.join("built").exists());
+
+    //release names have a one second resolution
+    thread::sleep(Duration::from_secs(1));
+    ettac(&dir, &["local"], true).failure();
+    git(&repository, &["checkout", "--quiet", "release"]);
+    git(
+        &repository,
+        &["commit", "--quiet", "--allow-empty", "-m", "hotfix"],
+    );
+
+    ettac(&dir, &["resume", "local"], false)
+        .failure()
+        .stderr(predicate::str::contains(
+            "the repository of host `local` moved from",
+        ));
+
+    fs::remove_dir_all(&dir).unwrap();
+    fs::remove_file("tests/resume/.ettac/progress/local.json").unwrap();
+}
+
+#[test]
+fn test_resume_runs_skipped_tasks() {
+    let dir = std::env::temp_dir().join(format!("ettac-resume-skipped-{}", std::process::id()));
+    create_repository(&dir.join("repository"));
+
+    ettac(&dir, &["--skip", "notes", "team/web"], true)
+        .failure()
+        .stdout(predicate::str::contains(
+            "Skipping task notes, excluded by --skip",
+        ));
+
+    //the name of the host is encoded rather than making a directory
+    let progress = Path::new("tests/resume/.ettac/progress/team%2Fweb.json");
+    assert!(progress.exists());
+
+    ettac(&dir, &["resume", "team/web"], false)
+        .success()
+        .stdout(predicate::str::contains(
+            "Skipping task checkout, completed before the deploy was resumed",
+        ))
+        .stdout(predicate::str::contains("Running task notes"))
+        .stdout(predicate::str::contains("Running task build"));
+
+    assert!(!progress.exists());
+    fs::remove_dir_all(&dir).unwrap();
+}
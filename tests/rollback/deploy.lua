@@ -0,0 +1,28 @@
+function setup()
+    default({
+        recipe = Recipe,
+        repository = env("REPOSITORY"),
+        path = env("DEPLOY_PATH"),
+        keep_releases = 1,
+        auto_rollback = true,
+    })
+
+    host("local", {})
+end
+
+Recipe = {}
+
+function Recipe:new()
+    return setmetatable({}, self)
+end
+
+function Recipe:notify()
+    if env("FAIL_NOTIFY") then
+        error("notification failed")
+    end
+end
+
+function Recipe:describe()
+    use(System:new())
+    task(self.notify, { phase = "finalize" })
+end
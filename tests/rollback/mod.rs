@@ -0,0 +1,84 @@
+use assert_cmd::assert::Assert;
+use assert_cmd::{Command, cargo_bin};
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+fn deploy(path: &Path, fail: bool) -> Assert {
+    deploy_with(path, fail, &[])
+}
+
+fn deploy_with(path: &Path, fail: bool, overrides: &[&str]) -> Assert {
+    let mut command = Command::new(cargo_bin!());
+    command
+        .current_dir("tests/rollback")
+        .args(overrides)
+        .arg("local")
+        .env("REPOSITORY", env!("CARGO_MANIFEST_DIR"))
+        .env("DEPLOY_PATH", path);
+
+    if fail {
+        command.env("FAIL_NOTIFY", "1");
+    }
+
+    command.assert()
+}
+
+#[test]
+fn test_auto_rollback_after_cleanup() {
+    let path = std::env::temp_dir().join(format!("ettac-rollback-{}", std::process::id()));
+
+    deploy(&path, false).success();
+    let previous = fs::read_link(path.join("current")).unwrap();
+
+    //release names have a one second resolution
+    thread::sleep(Duration::from_secs(1));
+
+    deploy(&path, true)
+        .failure()
+        .stdout(predicate::str::contains("Rolling back host local"))
+        .stdout(predicate::str::contains("ettac resume").not())
+        .stderr(predicate::str::contains("notification failed"));
+
+    assert_eq!(fs::read_link(path.join("current")).unwrap(), previous);
+    assert!(path.join(&previous).is_dir());
+
+    //the switch was undone so there is nothing left to resume
+    Command::new(cargo_bin!())
+        .current_dir("tests/rollback")
+        .args(["resume", "local"])
+        .env("REPOSITORY", env!("CARGO_MANIFEST_DIR"))
+        .env("DEPLOY_PATH", &path)
+        .assert()
+        .failure()
+        .stderr("no failed deploy of host `local` to resume\n");
+
+    fs::remove_dir_all(&path).unwrap();
+}
+
+#[test]
+fn test_rollback_on_failure_of_the_failing_host() {
+    let path = std::env::temp_dir().join(format!("ettac-rollout-{}", std::process::id()));
+    let overrides = [
+        "-o",
+        "auto_rollback=false",
+        "-o",
+        "rollback_on_failure=true",
+    ];
+
+    deploy_with(&path, false, &overrides).success();
+    let previous = fs::read_link(path.join("current")).unwrap();
+
+    thread::sleep(Duration::from_secs(1));
+
+    deploy_with(&path, true, &overrides)
+        .failure()
+        .stdout(predicate::str::contains("Rolling back host local"))
+        .stderr(predicate::str::contains("notification failed"));
+
+    assert_eq!(fs::read_link(path.join("current")).unwrap(), previous);
+
+    fs::remove_dir_all(&path).unwrap();
+}
@@ -0,0 +1,26 @@
+function setup()
+    default({
+        recipe = Recipe,
+        repository = env("REPOSITORY"),
+        path = env("DEPLOY_PATH"),
+        keep_releases = 1,
+    })
+
+    host("local", {})
+end
+
+Recipe = {}
+
+function Recipe:new()
+    return setmetatable({}, self)
+end
+
+function Recipe:stamp()
+    print("Stamped release " .. remote("basename \"$(pwd)\""))
+    remote("test -d ../../.ettac.lock")
+end
+
+function Recipe:describe()
+    use(System:new())
+    task(self.stamp)
+end
@@ -0,0 +1,52 @@
+use assert_cmd::{Command, cargo_bin};
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+
+fn ettac(path: &Path) -> Command {
+    let mut command = Command::new(cargo_bin!());
+    command
+        .current_dir("tests/run_task")
+        .env("REPOSITORY", env!("CARGO_MANIFEST_DIR"))
+        .env("DEPLOY_PATH", path);
+
+    command
+}
+
+#[test]
+fn test_run_task_takes_the_lock() {
+    let path = std::env::temp_dir().join(format!("ettac-run-task-{}", std::process::id()));
+    let lock = path.join(".ettac.lock");
+
+    ettac(&path).arg("local").assert().success();
+    let current = fs::read_link(path.join("current")).unwrap();
+    let release = current.file_name().unwrap().to_str().unwrap();
+
+    //the task checks that the lock is held while it runs
+    ettac(&path)
+        .args(["run-task", "local", "stamp"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!(
+            "Stamped release {}\n",
+            release
+        )));
+
+    assert!(!lock.exists());
+
+    fs::create_dir(&lock).unwrap();
+    ettac(&path)
+        .args(["run-task", "local", "stamp"])
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("Stamped release").not())
+        .stderr(format!(
+            "another deploy of host `local` is running, remove {}/.ettac.lock if it is not the case\n",
+            path.display()
+        ));
+
+    //the lock of the other deploy is left alone
+    assert!(lock.is_dir());
+
+    fs::remove_dir_all(&path).unwrap();
+}
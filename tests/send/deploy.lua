@@ -0,0 +1,39 @@
+function setup()
+    default({
+        recipe = Recipe,
+        repository = "https://github.com/mbenoukaiss/ettac.git",
+        path = env("DEPLOY_PATH"),
+        vars = { assets = "assets" },
+    })
+
+    host("local", {})
+    host("missing", { vars = { assets = "missing" } })
+
+    host("remote", {
+        hostname = "127.0.0.1",
+        port = 7122,
+        user = "bob",
+        private_key = env("PRIVATE_KEY"),
+    })
+end
+
+Recipe = {}
+
+function Recipe:new()
+    return setmetatable({}, self)
+end
+
+function Recipe:upload()
+    remote("mkdir -p " .. shell_quote(deploy.release_path), { cwd = "/" })
+
+    send("{{assets}}", "public")
+    send("assets/app.css")
+
+    print("Sent " .. remote("cat public/app.css public/js/app.js assets/app.css | tr '\\n' ' '"))
+    print("Mode " .. remote("stat -c %a public/js/run.sh"))
+    print("Link " .. remote("readlink public/latest.css"))
+end
+
+function Recipe:describe()
+    task(self.upload)
+end
@@ -0,0 +1,51 @@
+use crate::BOB_PRIVATE_KEY;
+use assert_cmd::assert::Assert;
+use assert_cmd::{Command, cargo_bin};
+use predicates::prelude::*;
+use std::fs;
+use std::path::Path;
+
+fn deploy(host: &str, path: &Path) -> Assert {
+    Command::new(cargo_bin!())
+        .current_dir("tests/send")
+        .arg(host)
+        .env("DEPLOY_PATH", path)
+        .env("PRIVATE_KEY", BOB_PRIVATE_KEY)
+        .assert()
+}
+
+fn uploaded(assert: Assert) {
+    assert
+        .success()
+        .stdout(predicate::str::contains("Sent body {} app() body {}\n"))
+        .stdout(predicate::str::contains("Mode 755\n"))
+        .stdout(predicate::str::contains("Link app.css\n"));
+}
+
+#[test]
+fn test_send_locally() {
+    let path = std::env::temp_dir().join(format!("ettac-send-{}", std::process::id()));
+
+    uploaded(deploy("local", &path));
+
+    fs::remove_dir_all(&path).unwrap();
+}
+
+#[test]
+fn test_send_missing_file() {
+    let path = std::env::temp_dir().join(format!("ettac-send-missing-{}", std::process::id()));
+
+    deploy("missing", &path)
+        .failure()
+        .stderr(predicate::str::contains(
+            "could not send `missing` to `public`: io error: No such file or directory",
+        ));
+
+    fs::remove_dir_all(&path).unwrap();
+}
+
+#[test]
+#[cfg_attr(not(feature = "integration"), ignore)]
+fn test_send_over_ssh() {
+    uploaded(deploy("remote", Path::new("/tmp/ettac-send")));
+}
@@ -0,0 +1,20 @@
+function setup()
+    default({
+        recipe = Recipe,
+        repository = "https://github.com/mbenoukaiss/ettac.git",
+        path = "/var/www/ettac",
+    })
+
+    host("web", { ssh_alias = "prod-web" })
+    host("worker", { ssh_alias = "prod-worker", user = "deployer", port = 2222 })
+end
+
+Recipe = {}
+
+function Recipe:new()
+    return setmetatable({}, self)
+end
+
+function Recipe:describe()
+    use(System:new())
+end
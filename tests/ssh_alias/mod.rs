@@ -0,0 +1,30 @@
+use assert_cmd::{Command, cargo_bin};
+use predicates::prelude::*;
+
+#[test]
+fn test_ssh_alias_hosts() {
+    Command::new(cargo_bin!())
+        .current_dir("tests/ssh_alias")
+        .arg("hosts")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "web     -      prod-web (ssh config)                   /var/www/ettac",
+        ))
+        .stdout(predicate::str::contains(
+            "worker  -      deployer@prod-worker:2222 (ssh config)  /var/www/ettac",
+        ));
+
+    //what the alias leaves out is resolved from the ssh config on connect
+    Command::new(cargo_bin!())
+        .current_dir("tests/ssh_alias")
+        .args(["hosts", "--json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "\"ssh\": {\n      \"alias\": \"prod-web\",\n      \"connect_retries\": 2,\n      \
+             \"connect_timeout\": 30,\n      \"credential\": \"ssh config\",\n      \
+             \"hostname\": null,\n      \"keepalive_interval\": null,\n      \
+             \"port\": null,\n      \"user\": null\n    }",
+        ));
+}
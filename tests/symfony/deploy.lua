@@ -1,21 +1,30 @@
 function setup()
     default({
-        recipe = Recipe.new,
+        recipe = Recipe,
         repository = "https://github.com/mbenoukaiss/ettac.git",
         keep_releases = 3,
         persistent_files = { ".env" },
         persistent_dirs = { "storage" },
+        env = { APP_ENV = "prod" },
+        vars = { php = { bin = "php8.4" } },
+        connect_timeout = 10,
+        keepalive_interval = 60,
+    })
+
+    stage("production", {
+        labels = { "prod" },
+        keep_releases = 5,
     })
 
     host("prod", {
+        stage = "production",
         hostname = "127.0.0.1",
         port = 7122,
         user = "bob",
         private_key = env("PRIVATE_KEY"),
         path = "/var/www/ettac",
-        labels = { "prod" },
-
-        keep_releases = 5,
+        vars = { php = { bin = "php8.3" } },
+        persistent_dirs = extend { "var/log" },
     })
 
     host("staging", {
@@ -86,5 +95,57 @@ function Recipe:describe()
         continue(); -- resumes execution after a task failure
     end)
 
+    catch(self.doctrine_post_migrations, function()
+        print("Nothing to run after the migrations");
+        continue();
+    end)
+
     after(system.fail, self.send_email)
 end
+
+Symfony = {}
+Symfony.__index = Symfony
+
+function Symfony:new(options)
+    return setmetatable({ options = options }, Symfony)
+end
+
+function Symfony:composer_install()
+    remote("echo {{php.bin}} composer.phar install --working-dir={{release_path}}")
+end
+
+function Symfony:doctrine_migrations()
+    remote("echo doctrine migrations")
+end
+
+function Symfony:build_frontend_assets()
+    remote("echo build frontend assets")
+end
+
+function Symfony:supervisor_restart()
+    remote("echo supervisor restart")
+end
+
+function Symfony:describe()
+    task(self.composer_install)
+    task(self.doctrine_migrations, { once = true })
+    task(self.build_frontend_assets)
+    task(self.supervisor_restart, { phase = "finalize" })
+end
+
+Crontab = {}
+Crontab.__index = Crontab
+
+function Crontab:new(entries)
+    return setmetatable({ entries = entries }, Crontab)
+end
+
+function Crontab:setup()
+    for _, entry in ipairs(self.entries) do
+        remote(shell_join({ "echo", entry[1] .. " " .. entry[2] }) .. " | cat")
+    end
+end
+
+function Crontab:describe()
+    task(self.setup, { on = { "prod" } })
+end
@@ -19,3 +19,177 @@ fn test_symfony_recipe() {
         .success()
         .stdout(expected_output);
 }
+
+#[test]
+fn test_symfony_dry_run() {
+    Command::new(cargo_bin!())
+        .current_dir("tests/symfony")
+        .arg("--dry-run")
+        .arg("production")
+        .arg("staging")
+        .env("PASSWORD", "y0zHDHwv3X31")
+        .env("PRIVATE_KEY", BOB_PRIVATE_KEY)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Planning host prod"))
+        .stdout(predicate::str::contains(
+            "symlink in /var/www/ettac: current -> releases/",
+        ))
+        .stdout(predicate::str::contains(
+            "output of #24 is read by the script",
+        ))
+        .stdout(predicate::str::contains(
+            "echo php8.3 composer.phar install --working-dir=/var/www/ettac/releases/",
+        ))
+        .stdout(predicate::str::contains(
+            "Skipping task setup, host has none of the labels prod",
+        ))
+        .stdout(predicate::str::contains(
+            "Skipping task doctrine_migrations, already ran on another host",
+        ))
+        .stdout(predicate::str::contains("Running command").not());
+}
+
+#[test]
+fn test_symfony_hosts() {
+    Command::new(cargo_bin!())
+        .current_dir("tests/symfony")
+        .arg("hosts")
+        .arg("--json")
+        .env("PASSWORD", "y0zHDHwv3X31")
+        .env("PRIVATE_KEY", BOB_PRIVATE_KEY)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"key\": \"<redacted>\""))
+        .stdout(predicate::str::contains("\"password\": \"<redacted>\""))
+        .stdout(predicate::str::contains("\"storage\",\n      \"var/log\""))
+        .stdout(predicate::str::contains("\"APP_ENV\": \"<redacted>\""))
+        .stdout(predicate::str::contains("\"php.bin\": \"<redacted>\""))
+        .stdout(predicate::str::contains("y0zHDHwv3X31").not())
+        .stdout(predicate::str::contains("PRIVATE KEY").not());
+}
+
+#[test]
+fn test_symfony_overrides() {
+    Command::new(cargo_bin!())
+        .current_dir("tests/symfony")
+        .args(["-o", "keep_releases=10", "-o", "vars.branch=hotfix"])
+        .arg("hosts")
+        .arg("--json")
+        .arg("--show-secrets")
+        .env("PASSWORD", "y0zHDHwv3X31")
+        .env("PRIVATE_KEY", BOB_PRIVATE_KEY)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\"keep_releases\": 10"))
+        .stdout(predicate::str::contains("\"branch\": \"hotfix\""))
+        .stdout(predicate::str::contains("\"keep_releases\": 5").not());
+
+    Command::new(cargo_bin!())
+        .current_dir("tests/symfony")
+        .args(["-o", "port=ssh"])
+        .arg("hosts")
+        .env("PASSWORD", "y0zHDHwv3X31")
+        .env("PRIVATE_KEY", BOB_PRIVATE_KEY)
+        .assert()
+        .failure()
+        .stderr(
+            "invalid deploy config in setup(): invalid override `port`: expected a port number\n",
+        );
+}
+
+#[test]
+fn test_symfony_tasks() {
+    Command::new(cargo_bin!())
+        .current_dir("tests/symfony")
+        .args(["tasks", "prod"])
+        .env("PASSWORD", "y0zHDHwv3X31")
+        .env("PRIVATE_KEY", BOB_PRIVATE_KEY)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "├── setup\n│   ├── lock (System, built-in) [rerun]\n",
+        ))
+        .stdout(predicate::str::contains(
+            "│   ├── doctrine_migrations (Symfony) [once]\n",
+        ))
+        .stdout(predicate::str::contains(
+            "│   └── doctrine_post_migrations (recipe)\n",
+        ))
+        .stdout(predicate::str::contains("supervisor_restart").not());
+
+    Command::new(cargo_bin!())
+        .current_dir("tests/symfony")
+        .args(["tasks", "prod", "--format", "dot"])
+        .env("PASSWORD", "y0zHDHwv3X31")
+        .env("PRIVATE_KEY", BOB_PRIVATE_KEY)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "task0 [label=\"lock\\nSystem, built-in\", style=dashed];",
+        ));
+}
+
+#[test]
+fn test_symfony_run_task() {
+    Command::new(cargo_bin!())
+        .current_dir("tests/symfony")
+        .args(["--dry-run", "run-task", "prod", "build_frontend_assets"])
+        .arg("--with-dependencies")
+        .env("PASSWORD", "y0zHDHwv3X31")
+        .env("PRIVATE_KEY", BOB_PRIVATE_KEY)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Running doctrine_migrations, build_frontend_assets on host prod in release <current>",
+        ))
+        .stdout(predicate::str::contains(
+            "remote in /var/www/ettac/releases/<current>: echo build frontend assets",
+        ))
+        .stdout(predicate::str::contains("Running task composer_install").not());
+
+    Command::new(cargo_bin!())
+        .current_dir("tests/symfony")
+        .args(["--dry-run", "run-task", "prod", "doctrine_migraton"])
+        .env("PASSWORD", "y0zHDHwv3X31")
+        .env("PRIVATE_KEY", BOB_PRIVATE_KEY)
+        .assert()
+        .failure()
+        .stderr("unknown task `doctrine_migraton`, did you mean `doctrine_migrations`?\n");
+}
+
+#[test]
+fn test_symfony_task_filters() {
+    Command::new(cargo_bin!())
+        .current_dir("tests/symfony")
+        .args(["--dry-run", "prod"])
+        .args([
+            "--only",
+            "build_frontend_assets",
+            "--skip",
+            "doctrine_setup",
+        ])
+        .env("PASSWORD", "y0zHDHwv3X31")
+        .env("PRIVATE_KEY", BOB_PRIVATE_KEY)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Running task lock"))
+        .stdout(predicate::str::contains(
+            "Running task build_frontend_assets",
+        ))
+        .stdout(predicate::str::contains(
+            "Skipping task composer_install, not selected by --only",
+        ))
+        .stdout(predicate::str::contains(
+            "Skipping task doctrine_setup, excluded by --skip",
+        ));
+
+    Command::new(cargo_bin!())
+        .current_dir("tests/symfony")
+        .args(["--dry-run", "--skip", "nope", "prod"])
+        .env("PASSWORD", "y0zHDHwv3X31")
+        .env("PRIVATE_KEY", BOB_PRIVATE_KEY)
+        .assert()
+        .failure()
+        .stderr("host `prod`: unknown task `nope`\n");
+}
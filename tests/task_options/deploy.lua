@@ -0,0 +1,40 @@
+function setup()
+    default({
+        recipe = Recipe,
+        repository = "https://github.com/mbenoukaiss/ettac.git",
+        path = "/tmp/ettac",
+    })
+
+    host("web", { labels = { "web" } })
+end
+
+Recipe = {}
+
+function Recipe:new()
+    return setmetatable({ attempts = 0 }, self)
+end
+
+function Recipe:migrations()
+    remote("echo migrations")
+end
+
+function Recipe:download()
+    self.attempts = self.attempts + 1
+    if self.attempts < 3 then
+        error("download interrupted")
+    end
+
+    remote("echo downloaded")
+end
+
+function Recipe:describe()
+    use(System:new())
+
+    task(self.migrations, {
+        when = function(deploy)
+            return deploy.labels[1] == "db"
+        end,
+    })
+
+    task(self.download, { retries = 2, retry_delay = 5, timeout = 60 })
+end
@@ -0,0 +1,22 @@
+use assert_cmd::{Command, cargo_bin};
+use predicates::prelude::*;
+
+#[test]
+fn test_task_options() {
+    Command::new(cargo_bin!())
+        .current_dir("tests/task_options")
+        .args(["--dry-run", "web"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "Skipping task migrations, its `when` condition is false",
+        ))
+        .stdout(predicate::str::contains("echo migrations").not())
+        .stdout(predicate::str::contains(
+            "Retrying task download (1/2) in 5s",
+        ))
+        .stdout(predicate::str::contains(
+            "Retrying task download (2/2) in 5s",
+        ))
+        .stdout(predicate::str::contains("echo downloaded"));
+}
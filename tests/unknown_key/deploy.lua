@@ -0,0 +1,22 @@
+function setup()
+    default({
+        recipe = Recipe,
+        repository = "https://github.com/mbenoukaiss/ettac.git",
+        vars = { domain = "example.com" },
+    })
+
+    host("prod", {
+        path = "/tmp/ettac",
+        persistant_dirs = { "storage" },
+    })
+end
+
+Recipe = {}
+
+function Recipe:new()
+    return setmetatable({}, self)
+end
+
+function Recipe:describe()
+    use(System:new())
+end
@@ -0,0 +1,15 @@
+use assert_cmd::{Command, cargo_bin};
+
+#[test]
+fn test_unknown_key() {
+    Command::new(cargo_bin!())
+        .current_dir("tests/unknown_key")
+        .arg("prod")
+        .assert()
+        .failure()
+        .stdout("")
+        .stderr(
+            "invalid deploy config in setup(): unknown key `persistant_dirs` in host `prod`, \
+             did you mean `persistent_dirs`?\n",
+        );
+}
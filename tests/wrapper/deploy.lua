@@ -0,0 +1,46 @@
+dofile("../helpers.lua")
+
+function setup()
+    default({
+        recipe = Recipe,
+        repository = "https://github.com/mbenoukaiss/ettac.git",
+        path = "/tmp/ettac",
+    })
+
+    host("local", {})
+
+    host("remote", {
+        hostname = "127.0.0.1",
+        port = 7122,
+        user = "bob",
+        private_key = env("PRIVATE_KEY"),
+    })
+end
+
+Recipe = {}
+
+function Recipe:new()
+    return setmetatable({}, self)
+end
+
+function Recipe:probe()
+    try("missing", "true", { cwd = "/nonexistent" })
+    try("file", "true", { cwd = "/etc/passwd" })
+    try("forbidden", "true", { cwd = "/root" })
+    -- the test server accepts PATH, which is set before the wrapper runs as
+    -- it is for local commands
+    try("no shell", "true", { cwd = "/", env = { PATH = "/nonexistent" } })
+    try("status", "exit 70", { cwd = "/" })
+
+    local dir = "/tmp/ettac wrapper it's $(touch ~/pwned)"
+    remote(shell_join({ "mkdir", "-p", dir }), { cwd = "/" })
+    try("quoted", "pwd; echo \"it's $((1 + 1))\"", { cwd = dir })
+    try("injected", "test -e ~/pwned && echo yes || echo no", { cwd = "/" })
+    remote(shell_join({ "rm", "-rf", dir }), { cwd = "/" })
+
+    try("marker", "printf '%s' __ettac_started__; echo done", { cwd = "/" })
+end
+
+function Recipe:describe()
+    task(self.probe)
+end
@@ -0,0 +1,50 @@
+use crate::BOB_PRIVATE_KEY;
+use assert_cmd::assert::Assert;
+use assert_cmd::{Command, cargo_bin};
+use predicates::prelude::*;
+
+fn probe(host: &str) -> Assert {
+    Command::new(cargo_bin!())
+        .current_dir("tests/wrapper")
+        .arg(host)
+        .env("PRIVATE_KEY", BOB_PRIVATE_KEY)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "missing failed: access error: Directory `/nonexistent` not found\n",
+        ))
+        .stdout(predicate::str::contains(
+            "file failed: access error: `/etc/passwd` is not a directory\n",
+        ))
+        .stdout(predicate::str::contains(
+            "no shell failed: access error: No `sh` shell found to run the command\n",
+        ))
+        .stdout(predicate::str::contains(
+            "status failed: command `exit 70` exited with status 70",
+        ))
+        .stdout(predicate::str::contains(
+            "quoted: /tmp/ettac wrapper it's $(touch ~/pwned)\nit's 2\n",
+        ))
+        .stdout(predicate::str::contains("injected: no\n"))
+        .stdout(predicate::str::contains("marker: __ettac_started__done\n"))
+}
+
+#[test]
+fn test_local_wrapper() {
+    let assert = probe("local");
+
+    //root can enter any directory
+    if unsafe { libc::geteuid() } != 0 {
+        assert.stdout(predicate::str::contains(
+            "forbidden failed: access error: Permission denied to enter directory `/root`\n",
+        ));
+    }
+}
+
+#[test]
+#[cfg_attr(not(feature = "integration"), ignore)]
+fn test_wrapper() {
+    probe("remote").stdout(predicate::str::contains(
+        "forbidden failed: access error: Permission denied to enter directory `/root`\n",
+    ));
+}